@@ -0,0 +1,68 @@
+#!/usr/bin/env bash
+
+set -u
+
+ROOT="tests/inputs"
+OUT_DIR="tests/expected"
+
+[[ ! -d "$OUT_DIR" ]] && mkdir -p "$OUT_DIR"
+
+EMPTY="$ROOT/empty.txt"
+FOX="$ROOT/fox.txt"
+SPIDERS="$ROOT/spiders.txt"
+BUSTLE="$ROOT/the-bustle.txt"
+NO_NEWLINE="$ROOT/no-newline.txt"
+LATIN1="$ROOT/latin1.txt"
+ALL="$EMPTY $FOX $SPIDERS $BUSTLE"
+
+for FILE in $ALL $NO_NEWLINE $LATIN1; do
+    BASENAME=$(basename "$FILE")
+    cat    $FILE > ${OUT_DIR}/${BASENAME}.out
+    cat -n $FILE > ${OUT_DIR}/${BASENAME}.n.out
+    cat -b $FILE > ${OUT_DIR}/${BASENAME}.b.out
+done
+
+cat    $ALL > $OUT_DIR/all.out
+cat -n $ALL > $OUT_DIR/all.n.out
+cat -b $ALL > $OUT_DIR/all.b.out
+
+cat -n $NO_NEWLINE $FOX > $OUT_DIR/joined.n.out
+
+cat    < $BUSTLE > $OUT_DIR/$(basename $BUSTLE).stdin.out
+cat -n < $BUSTLE > $OUT_DIR/$(basename $BUSTLE).n.stdin.out
+cat -b < $BUSTLE > $OUT_DIR/$(basename $BUSTLE).b.stdin.out
+
+CONTROL="$ROOT/control.txt"
+for FLAG in v E T A e t; do
+    cat -$FLAG $CONTROL > $OUT_DIR/$(basename $CONTROL).$FLAG.out
+done
+cat -vn $CONTROL > $OUT_DIR/$(basename $CONTROL).vn.out
+cat -Ab $CONTROL > $OUT_DIR/$(basename $CONTROL).Ab.out
+
+BLANKS="$ROOT/blanks.txt"
+cat -s  $BLANKS > $OUT_DIR/$(basename $BLANKS).s.out
+cat -sn $BLANKS > $OUT_DIR/$(basename $BLANKS).sn.out
+cat -sb $BLANKS > $OUT_DIR/$(basename $BLANKS).sb.out
+cat -s  $BLANKS $BLANKS > $OUT_DIR/blanks.twice.s.out
+
+# GNU cat can only number across files, so build the per-file and
+# custom start/width variants from single-file runs.
+(cat -n $FOX; cat -n $SPIDERS; cat -n $BUSTLE) > $OUT_DIR/all.n.per-file.out
+nl -ba -v 100 -w 4 $SPIDERS > $OUT_DIR/spiders.txt.n100w4.out
+
+# Compressed fixtures are checked in; these are their plain contents.
+gzip -dc $ROOT/multi.gz > $OUT_DIR/multi.gz.out
+cat $FOX $BUSTLE > $OUT_DIR/fox-bustle.out
+
+xxd $CONTROL > $OUT_DIR/$(basename $CONTROL).x.out
+xxd -g 4 -c 6 $CONTROL > $OUT_DIR/$(basename $CONTROL).x.g4c6.out
+xxd -g 3 -s 5 -l 20 $FOX > $OUT_DIR/$(basename $FOX).x.g3s5l20.out
+(xxd $FOX; xxd $BUSTLE) > $OUT_DIR/fox-bustle.x.out
+
+# Encoding fixtures are checked in; convert them with iconv and tr.
+iconv -f UTF-16 -t UTF-8 $ROOT/bustle-utf16le.txt > $OUT_DIR/bustle-utf16le.txt.out
+iconv -f UTF-16 -t UTF-8 $ROOT/bustle-utf16le.txt | sed 's/\r$//' \
+    > $OUT_DIR/bustle-utf16le.txt.eol.out
+iconv -f LATIN1 -t UTF-8 $ROOT/latin1-crlf.txt | sed 's/\r$//' | tr '\r' '\n' \
+    > $OUT_DIR/latin1-crlf.txt.eol.out
+cat -n $OUT_DIR/latin1-crlf.txt.eol.out > $OUT_DIR/latin1-crlf.txt.eol.n.out
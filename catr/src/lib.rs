@@ -1,7 +1,15 @@
 use std::error::Error;
+use std::fmt::Write as _;
 use clap::{App, Arg};
 use std::fs::File;
-use std::io::{self, BufRead, BufReader};
+use std::io::{self, BufRead, BufReader, BufWriter, Cursor, Read, StdoutLock, Write};
+use std::ops::RangeBounds;
+
+use bzip2::read::MultiBzDecoder;
+use encoding_rs::Encoding;
+use encoding_rs_io::DecodeReaderBytesBuilder;
+use flate2::read::MultiGzDecoder;
+use xz2::read::XzDecoder;
 
 type MyResult<T> = Result<T, Box<dyn Error>>;
 
@@ -10,6 +18,41 @@ pub struct Config {
     files: Vec<String>,
     number_lines: bool,
     number_nonblank_lines: bool,
+    show_nonprinting: bool,
+    show_ends: bool,
+    show_tabs: bool,
+    squeeze_blank: bool,
+    number_per_file: bool,
+    number_start: usize,
+    number_width: usize,
+    decompress: bool,
+    hex: Option<HexConfig>,
+    encoding: Option<&'static Encoding>,
+    auto_encoding: bool,
+    normalize_eol: bool,
+}
+
+// Widest line number column, room for any line number with some to spare.
+const MAX_NUMBER_WIDTH: usize = 32;
+
+// Widest hex dump row, the same cap xxd puts on -c.
+const MAX_COLS: usize = 256;
+
+#[derive(Debug)]
+struct HexConfig {
+    cols: usize,
+    group: usize,
+    seek: u64,
+    length: Option<u64>,
+}
+
+// Numbering state shared by all inputs, so that numbering continues
+// across files and a last line without '\n' runs into the next file.
+struct Printer {
+    // None once the numbers have run past usize::MAX.
+    next_num: Option<usize>,
+    at_line_start: bool,
+    prev_blank: bool,
 }
 
 pub fn get_flags() -> MyResult<Config> {
@@ -20,13 +63,15 @@ pub fn get_flags() -> MyResult<Config> {
         .arg(
             Arg::with_name("files")
                 .value_name("FILE")
+                .help("Input file(s)")
                 .multiple(true)
-                .default_value("-"), 
+                .default_value("-"),
         )
         .arg(
             Arg::with_name("number")
             .short("n")
             .long("number")
+            .help("number all output lines")
             .takes_value(false)
             .conflicts_with("number_nonblank"),
         )
@@ -34,31 +79,603 @@ pub fn get_flags() -> MyResult<Config> {
             Arg::with_name("number_nonblank")
                 .short("b")
                 .long("number-nonblank")
+                .help("number nonempty output lines")
                 .takes_value(false),
         )
+        .arg(
+            Arg::with_name("show_all")
+                .short("A")
+                .long("show-all")
+                .help("equivalent to -vET"),
+        )
+        .arg(
+            Arg::with_name("e")
+                .short("e")
+                .help("equivalent to -vE"),
+        )
+        .arg(
+            Arg::with_name("show_ends")
+                .short("E")
+                .long("show-ends")
+                .help("display $ at end of each line"),
+        )
+        .arg(
+            Arg::with_name("t")
+                .short("t")
+                .help("equivalent to -vT"),
+        )
+        .arg(
+            Arg::with_name("show_tabs")
+                .short("T")
+                .long("show-tabs")
+                .help("display TAB characters as ^I"),
+        )
+        .arg(
+            Arg::with_name("show_nonprinting")
+                .short("v")
+                .long("show-nonprinting")
+                .help("use ^ and M- notation, except for LFD and TAB"),
+        )
+        .arg(
+            Arg::with_name("squeeze_blank")
+                .short("s")
+                .long("squeeze-blank")
+                .help("suppress repeated empty output lines"),
+        )
+        .arg(
+            Arg::with_name("number_per_file")
+                .long("number-per-file")
+                .help("restart line numbers at every file"),
+        )
+        .arg(
+            Arg::with_name("number_start")
+                .long("number-start")
+                .value_name("N")
+                .default_value("1")
+                .help("first line number"),
+        )
+        .arg(
+            Arg::with_name("number_width")
+                .long("number-width")
+                .value_name("WIDTH")
+                .default_value("6")
+                .help("width of the line number column, at most 32"),
+        )
+        .arg(
+            Arg::with_name("no_decompress")
+                .long("no-decompress")
+                .help("copy compressed input as is"),
+        )
+        .arg(
+            Arg::with_name("hex")
+                .short("x")
+                .long("hex")
+                .help("show input as an xxd-style hex dump")
+                .conflicts_with_all(&[
+                    "number", "number_nonblank", "show_all", "e", "show_ends",
+                    "t", "show_tabs", "show_nonprinting", "squeeze_blank",
+                ]),
+        )
+        .arg(
+            Arg::with_name("cols")
+                .long("cols")
+                .value_name("COLS")
+                .default_value("16")
+                .help("bytes per hex dump row, at most 256"),
+        )
+        .arg(
+            Arg::with_name("group_size")
+                .long("group-size")
+                .value_name("BYTES")
+                .default_value("2")
+                .help("bytes per hex dump group"),
+        )
+        .arg(
+            Arg::with_name("seek")
+                .long("seek")
+                .value_name("OFFSET")
+                .requires("hex")
+                .help("start the hex dump at byte OFFSET"),
+        )
+        .arg(
+            Arg::with_name("length")
+                .long("length")
+                .value_name("LEN")
+                .requires("hex")
+                .help("stop the hex dump after LEN bytes"),
+        )
+        .arg(
+            Arg::with_name("from_encoding")
+                .long("from-encoding")
+                .value_name("ENCODING")
+                .conflicts_with("auto_encoding")
+                .help("convert input from ENCODING (e.g. utf-16le, latin1) to UTF-8"),
+        )
+        .arg(
+            Arg::with_name("auto_encoding")
+                .long("auto-encoding")
+                .help("convert UTF-8 and UTF-16 input with a BOM to UTF-8"),
+        )
+        .arg(
+            Arg::with_name("normalize_eol")
+                .long("normalize-eol")
+                .help("rewrite CRLF and CR line endings as LF"),
+        )
         .get_matches();
 
+        let number_start = matches.value_of("number_start")
+            .map(|s| parse_int(s, "number start", 0..))
+            .transpose()?;
+        let number_width = matches.value_of("number_width")
+            .map(|s| parse_int(s, "number width", 1..=MAX_NUMBER_WIDTH))
+            .transpose()?;
+
+        let hex = if matches.is_present("hex") {
+            Some(HexConfig {
+                cols: parse_int(matches.value_of("cols").unwrap(), "column count", 1..=MAX_COLS)?,
+                group: parse_int(matches.value_of("group_size").unwrap(), "group size", 1..)?,
+                seek: matches.value_of("seek")
+                    .map(|s| parse_int(s, "seek offset", 0..))
+                    .transpose()?
+                    .unwrap_or(0) as u64,
+                length: matches.value_of("length")
+                    .map(|s| parse_int(s, "length", 0..))
+                    .transpose()?
+                    .map(|n| n as u64),
+            })
+        } else {
+            None
+        };
+
+        let encoding = matches.value_of("from_encoding")
+            .map(|label| {
+                Encoding::for_label(label.as_bytes())
+                    .ok_or_else(|| format!("unknown encoding -- {}", label))
+            })
+            .transpose()?;
+
+        let all = matches.is_present("show_all");
+        let e = matches.is_present("e");
+        let t = matches.is_present("t");
+
         Ok(Config {
             files: matches.values_of_lossy("files").unwrap(),
             number_lines: matches.is_present("number"),
             number_nonblank_lines: matches.is_present("number_nonblank"),
-        }) 
+            show_nonprinting: all || e || t || matches.is_present("show_nonprinting"),
+            show_ends: all || e || matches.is_present("show_ends"),
+            show_tabs: all || t || matches.is_present("show_tabs"),
+            squeeze_blank: matches.is_present("squeeze_blank"),
+            number_per_file: matches.is_present("number_per_file"),
+            number_start: number_start.unwrap(),
+            number_width: number_width.unwrap(),
+            decompress: !matches.is_present("no_decompress"),
+            hex,
+            encoding,
+            auto_encoding: matches.is_present("auto_encoding"),
+            normalize_eol: matches.is_present("normalize_eol"),
+        })
 }
 
-fn open(filename: &str) -> MyResult<Box<dyn BufRead>> {
-    match filename {
-        "-" => {Ok(Box::new(BufReader::new(io::stdin())))},
-        _ => {Ok(Box::new(BufReader::new(File::open(filename)?)))},
+fn parse_int(s: &str, what: &str, range: impl RangeBounds<usize>) -> MyResult<usize> {
+    match s.parse() {
+        Ok(n) if range.contains(&n) => Ok(n),
+        _ => Err(From::from(format!("illegal {} -- {}", what, s))),
+    }
+}
+
+fn open(filename: &str, config: &Config) -> io::Result<Box<dyn BufRead>> {
+    let mut file: Box<dyn Read> = match filename {
+        "-" => Box::new(io::stdin()),
+        _ => Box::new(File::open(filename)?),
+    };
+    if config.decompress {
+        file = decompressed(file)?;
+    }
+    if config.encoding.is_some() || config.auto_encoding {
+        // A BOM always wins; without one, --auto-encoding passes the
+        // bytes through as they are.
+        file = Box::new(
+            DecodeReaderBytesBuilder::new()
+                .encoding(config.encoding)
+                .bom_override(true)
+                .build(file),
+        );
+    }
+    if config.normalize_eol {
+        file = Box::new(NormalizeEol::new(BufReader::new(file)));
+    }
+    Ok(Box::new(BufReader::new(file)))
+}
+
+// Turns CRLF and lone CR into LF. A CR is written out as LF right away,
+// so the only state to carry between reads is whether to drop a LF that
+// follows it.
+struct NormalizeEol<R> {
+    inner: R,
+    after_cr: bool,
+}
+
+impl<R: BufRead> NormalizeEol<R> {
+    fn new(inner: R) -> Self {
+        NormalizeEol { inner, after_cr: false }
+    }
+}
+
+impl<R: BufRead> Read for NormalizeEol<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            let input = self.inner.fill_buf()?;
+            if input.is_empty() || buf.is_empty() {
+                return Ok(0);
+            }
+            let mut used = 0;
+            let mut written = 0;
+            for &byte in input {
+                if written == buf.len() {
+                    break;
+                }
+                used += 1;
+                match byte {
+                    b'\n' if self.after_cr => {}
+                    b'\r' => {
+                        buf[written] = b'\n';
+                        written += 1;
+                    }
+                    _ => {
+                        buf[written] = byte;
+                        written += 1;
+                    }
+                }
+                self.after_cr = byte == b'\r';
+            }
+            self.inner.consume(used);
+            // Only a dropped LF can leave nothing to return; read on
+            // rather than report a false end of file.
+            if written > 0 {
+                return Ok(written);
+            }
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Compression {
+    Gzip,
+    Bzip2,
+    Xz,
+    Zstd,
+}
+
+// The extension is ignored so stdin and misnamed files work too.
+const MAGIC: [(&[u8], Compression); 4] = [
+    (&[0x1f, 0x8b], Compression::Gzip),
+    (b"BZh", Compression::Bzip2),
+    (&[0xfd, b'7', b'z', b'X', b'Z', 0x00], Compression::Xz),
+    (&[0x28, 0xb5, 0x2f, 0xfd], Compression::Zstd),
+];
+
+// Reads the start of `file` until it matches a magic number or can no
+// longer begin one, so input that is slow to come (a terminal, a pipe)
+// isn't held up waiting for bytes no magic number needs.
+fn read_magic<R: Read>(file: &mut R) -> io::Result<Vec<u8>> {
+    // Room for the longest magic number, xz's.
+    let mut magic = vec![0; 6];
+    let mut len = 0;
+    while MAGIC.iter().any(|(m, _)| m.len() > len && m.starts_with(&magic[..len])) {
+        match file.read(&mut magic[len..]) {
+            Ok(0) => break,
+            Ok(n) => len += n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    magic.truncate(len);
+    Ok(magic)
+}
+
+fn compression(magic: &[u8]) -> Option<Compression> {
+    MAGIC.iter().find(|(m, _)| magic.starts_with(m)).map(|&(_, format)| format)
+}
+
+fn decoder(format: Compression, file: Box<dyn Read>) -> io::Result<Box<dyn Read>> {
+    Ok(match format {
+        Compression::Gzip => Box::new(MultiGzDecoder::new(file)),
+        Compression::Bzip2 => Box::new(MultiBzDecoder::new(file)),
+        Compression::Xz => Box::new(XzDecoder::new_multi_decoder(file)),
+        Compression::Zstd => Box::new(zstd::Decoder::new(file)?),
+    })
+}
+
+// Decodes `file` when it starts with a known magic number, otherwise
+// passes it through unchanged.
+fn decompressed(mut file: Box<dyn Read>) -> io::Result<Box<dyn Read>> {
+    let magic = read_magic(&mut file)?;
+    let format = compression(&magic);
+    let file = Box::new(Cursor::new(magic).chain(file));
+    match format {
+        Some(format) => decoder(format, file),
+        None => Ok(file),
+    }
+}
+
+// Read errors are reported and skip the rest of the input, while
+// write errors (e.g. a closed pipe) and running out of line numbers
+// stop catr altogether.
+enum CopyError {
+    Read(io::Error),
+    Write(io::Error),
+    Overflow,
+}
+
+impl Printer {
+    fn new(config: &Config) -> Printer {
+        Printer {
+            next_num: Some(config.number_start),
+            at_line_start: true,
+            prev_blank: false,
+        }
+    }
+
+    // Copies `file` to `out` byte for byte, adding line numbers as asked.
+    // Whatever has been read goes out before the next read, which may
+    // block, so input that trickles in (`tail -f`) shows up as it comes.
+    fn print<R: BufRead, W: Write>(
+        &mut self,
+        config: &Config,
+        file: &mut R,
+        out: &mut W,
+    ) -> Result<(), CopyError> {
+        let mut line = Vec::new();
+        let mut buf = Vec::new();
+        loop {
+            let chunk = match file.fill_buf() {
+                Ok([]) => break,
+                Ok(chunk) => chunk,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(CopyError::Read(e)),
+            };
+            let len = chunk.len();
+            for piece in chunk.split_inclusive(|&b| b == b'\n') {
+                line.extend_from_slice(piece);
+                if line.ends_with(b"\n") {
+                    self.print_line(config, &line, &mut buf, out)?;
+                    line.clear();
+                }
+            }
+            file.consume(len);
+
+            // Hold back a trailing CR, which -E shows as "^M" only when a
+            // LF follows it.
+            let held = (config.show_ends && line.ends_with(b"\r")) as usize;
+            if line.len() > held {
+                let part = line.len() - held;
+                self.print_line(config, &line[..part], &mut buf, out)?;
+                line.drain(..part);
+            }
+            out.flush().map_err(CopyError::Write)?;
+        }
+        if !line.is_empty() {
+            self.print_line(config, &line, &mut buf, out)?;
+        }
+        Ok(())
+    }
+
+    // Writes a line, or the part of one read so far.
+    fn print_line<W: Write>(
+        &mut self,
+        config: &Config,
+        line: &[u8],
+        buf: &mut Vec<u8>,
+        out: &mut W,
+    ) -> Result<(), CopyError> {
+        buf.clear();
+        if self.at_line_start {
+            let blank = line == b"\n";
+            if blank && self.prev_blank && config.squeeze_blank {
+                return Ok(());
+            }
+            self.prev_blank = blank;
+            if config.number_lines || (config.number_nonblank_lines && !blank) {
+                let num = self.next_num.ok_or(CopyError::Overflow)?;
+                write!(buf, "{:width$}\t", num, width = config.number_width).unwrap();
+                self.next_num = num.checked_add(1);
+            }
+        }
+        if config.show_nonprinting || config.show_ends || config.show_tabs {
+            show_line(config, line, buf);
+        } else {
+            buf.extend_from_slice(line);
+        }
+        out.write_all(buf).map_err(CopyError::Write)?;
+        self.at_line_start = line.ends_with(b"\n");
+        Ok(())
+    }
+}
+
+// Appends `line` to `buf` with GNU cat's -v/-E/-T rendering.
+fn show_line(config: &Config, line: &[u8], buf: &mut Vec<u8>) {
+    let (mut body, newline) = match line.split_last() {
+        Some((b'\n', body)) => (body, true),
+        _ => (line, false),
+    };
+    // Like GNU cat, -E marks a CRLF ending as "^M$" even without -v.
+    let crlf = newline && config.show_ends && body.last() == Some(&b'\r');
+    if crlf {
+        body = &body[..body.len() - 1];
+    }
+    for &byte in body {
+        match byte {
+            b'\t' if config.show_tabs => buf.extend_from_slice(b"^I"),
+            b'\t' => buf.push(byte),
+            _ if config.show_nonprinting => show_nonprinting(byte, buf),
+            _ => buf.push(byte),
+        }
+    }
+    if crlf {
+        buf.extend_from_slice(b"^M");
+    }
+    if newline {
+        if config.show_ends {
+            buf.push(b'$');
+        }
+        buf.push(b'\n');
     }
 }
 
+// Caret notation for control bytes and M- for bytes with the high bit set.
+fn show_nonprinting(byte: u8, buf: &mut Vec<u8>) {
+    let low = if byte >= 128 {
+        buf.extend_from_slice(b"M-");
+        byte - 128
+    } else {
+        byte
+    };
+    match low {
+        0..=31 => buf.extend_from_slice(&[b'^', low + 64]),
+        127 => buf.extend_from_slice(b"^?"),
+        _ => buf.push(low),
+    }
+}
 
-pub fn run(config: Config) -> MyResult<()> {
-    for filename in config.files {
-        match open(&filename) {
-            Err(e) =>eprintln!("{}{}", filename, e),
-            Ok(_) => println!("{}", filename),
+// Writes `file` as rows of "offset: hex groups  ascii", laid out like xxd.
+fn hex_dump<R: Read, W: Write>(
+    hex: &HexConfig,
+    file: &mut R,
+    out: &mut W,
+) -> Result<(), CopyError> {
+    io::copy(&mut file.take(hex.seek), &mut io::sink()).map_err(CopyError::Read)?;
+    let mut file = file.take(hex.length.unwrap_or(u64::MAX));
+    let width = hex.cols * 2 + hex.cols.div_ceil(hex.group);
+    let mut offset = hex.seek;
+    let mut row = Vec::with_capacity(hex.cols);
+    let mut buf = String::new();
+    loop {
+        row.clear();
+        buf.clear();
+        (&mut file)
+            .take(hex.cols as u64)
+            .read_to_end(&mut row)
+            .map_err(CopyError::Read)?;
+        if row.is_empty() {
+            break;
+        }
+        write!(buf, "{:08x}: ", offset).unwrap();
+        let start = buf.len();
+        for (i, byte) in row.iter().enumerate() {
+            write!(buf, "{:02x}", byte).unwrap();
+            if (i + 1) % hex.group == 0 {
+                buf.push(' ');
+            }
         }
+        let pad = width - (buf.len() - start) + 1;
+        buf.extend(std::iter::repeat_n(' ', pad));
+        buf.extend(row.iter().map(|&b| {
+            if (0x20..0x7f).contains(&b) { b as char } else { '.' }
+        }));
+        buf.push('\n');
+        out.write_all(buf.as_bytes()).map_err(CopyError::Write)?;
+        offset += row.len() as u64;
     }
     Ok(())
-}
\ No newline at end of file
+}
+
+// Copies one input to `out` untouched. Between a file or stdin and stdout,
+// `io::copy` uses copy_file_range, sendfile or splice on Linux, and falls
+// back to a plain copy through `out`'s buffer when those don't apply.
+fn copy_plain(
+    filename: &str,
+    decompress: bool,
+    out: &mut BufWriter<StdoutLock<'static>>,
+) -> Result<(), CopyError> {
+    match filename {
+        "-" => copy_raw(io::stdin().lock(), decompress, out),
+        _ => copy_raw(File::open(filename).map_err(CopyError::Read)?, decompress, out),
+    }
+}
+
+fn copy_raw<R: Read + 'static>(
+    mut file: R,
+    decompress: bool,
+    out: &mut BufWriter<StdoutLock<'static>>,
+) -> Result<(), CopyError> {
+    if decompress {
+        let magic = read_magic(&mut file).map_err(CopyError::Read)?;
+        if let Some(format) = compression(&magic) {
+            let file = Box::new(Cursor::new(magic).chain(file));
+            let mut file = decoder(format, file).map_err(CopyError::Read)?;
+            return copy_by_hand(&mut file, out);
+        }
+        out.write_all(&magic).map_err(CopyError::Write)?;
+    }
+    // `io::copy` doesn't say which side failed. Nothing is lost when it
+    // fails, so carry on by hand from there: an error that persists then
+    // shows where it comes from.
+    match io::copy(&mut file, out) {
+        Ok(_) => Ok(()),
+        Err(_) => copy_by_hand(&mut file, out),
+    }
+}
+
+// A plain copy that keeps read and write errors apart.
+fn copy_by_hand<R: Read, W: Write>(file: &mut R, out: &mut W) -> Result<(), CopyError> {
+    let mut buf = vec![0; 64 * 1024];
+    loop {
+        let n = match file.read(&mut buf) {
+            Ok(0) => return Ok(()),
+            Ok(n) => n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(CopyError::Read(e)),
+        };
+        out.write_all(&buf[..n]).map_err(CopyError::Write)?;
+    }
+}
+
+/// Returns `Ok(false)` when at least one input could not be read; the
+/// error has already been reported on stderr.
+pub fn run(config: Config) -> MyResult<bool> {
+    let mut out = BufWriter::with_capacity(128 * 1024, io::stdout().lock());
+    let plain = !(config.number_lines
+        || config.number_nonblank_lines
+        || config.squeeze_blank
+        || config.show_nonprinting
+        || config.show_ends
+        || config.show_tabs
+        || config.encoding.is_some()
+        || config.auto_encoding
+        || config.normalize_eol);
+    let mut printer = Printer::new(&config);
+    let mut all_ok = true;
+
+    for filename in &config.files {
+        if config.number_per_file {
+            printer.next_num = Some(config.number_start);
+        }
+        let result = if let Some(hex) = &config.hex {
+            open(filename, &config)
+                .map_err(CopyError::Read)
+                .and_then(|mut file| hex_dump(hex, &mut file, &mut out))
+        } else if plain {
+            copy_plain(filename, config.decompress, &mut out)
+        } else {
+            open(filename, &config)
+                .map_err(CopyError::Read)
+                .and_then(|mut file| printer.print(&config, &mut file, &mut out))
+        };
+        match result {
+            Ok(()) => {}
+            Err(CopyError::Read(e)) => {
+                out.flush()?;
+                eprintln!("catr: {}: {}", filename, e);
+                all_ok = false;
+            }
+            Err(CopyError::Write(e)) => return Err(e.into()),
+            Err(CopyError::Overflow) => {
+                out.flush()?;
+                return Err(From::from("line number overflow"));
+            }
+        }
+    }
+    out.flush()?;
+    Ok(all_ok)
+}
@@ -1,6 +1,10 @@
 fn main() {
-    if let Ok(c) = catr::get_flags() {
-        println!("{:?}", c);
+    match catr::get_flags().and_then(catr::run) {
+        Ok(true) => {}
+        Ok(false) => std::process::exit(1),
+        Err(e) => {
+            eprintln!("catr: {}", e);
+            std::process::exit(1);
+        }
     }
-    catr::run(catr::get_flags().unwrap()).expect("fail");
 }
@@ -0,0 +1,674 @@
+use assert_cmd::Command;
+use predicates::prelude::*;
+use rand::{distributions::Alphanumeric, Rng};
+use std::error::Error;
+use std::fs;
+use std::io::{BufRead, BufReader, Write};
+use std::process::Stdio;
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+type TestResult = Result<(), Box<dyn Error>>;
+
+const PRG: &str = "catr";
+const EMPTY: &str = "tests/inputs/empty.txt";
+const FOX: &str = "tests/inputs/fox.txt";
+const SPIDERS: &str = "tests/inputs/spiders.txt";
+const BUSTLE: &str = "tests/inputs/the-bustle.txt";
+const NO_NEWLINE: &str = "tests/inputs/no-newline.txt";
+const LATIN1: &str = "tests/inputs/latin1.txt";
+const CONTROL: &str = "tests/inputs/control.txt";
+const BLANKS: &str = "tests/inputs/blanks.txt";
+const GZ: &str = "tests/inputs/the-bustle.txt.gz";
+const BZ2: &str = "tests/inputs/the-bustle.txt.bz2";
+const XZ: &str = "tests/inputs/the-bustle.txt.xz";
+const ZST: &str = "tests/inputs/the-bustle.txt.zst";
+
+// --------------------------------------------------
+#[test]
+fn usage() -> TestResult {
+    for flag in &["-h", "--help"] {
+        Command::cargo_bin(PRG)?
+            .arg(flag)
+            .assert()
+            .stdout(predicate::str::contains("USAGE"));
+    }
+    Ok(())
+}
+
+// --------------------------------------------------
+fn gen_bad_file() -> String {
+    loop {
+        let filename: String = rand::thread_rng()
+            .sample_iter(&Alphanumeric)
+            .take(7)
+            .map(char::from)
+            .collect();
+
+        if fs::metadata(&filename).is_err() {
+            return filename;
+        }
+    }
+}
+
+// --------------------------------------------------
+#[test]
+fn skips_bad_file() -> TestResult {
+    let bad = gen_bad_file();
+    let expected = format!("catr: {}: .* [(]os error 2[)]", bad);
+    Command::cargo_bin(PRG)?
+        .args([&bad, FOX])
+        .assert()
+        .failure()
+        .stdout(fs::read_to_string("tests/expected/fox.txt.out")?)
+        .stderr(predicate::str::is_match(expected)?);
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn skips_directory() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["tests", FOX])
+        .assert()
+        .failure()
+        .stdout(fs::read_to_string("tests/expected/fox.txt.out")?)
+        .stderr(predicate::str::starts_with("catr: tests: "));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn stops_on_write_error() -> TestResult {
+    // With SIGXFSZ ignored, writing past `ulimit -f` fails with EFBIG. That
+    // is the output failing, so catr must stop rather than blame the input
+    // and go on to the next file.
+    let dir = std::env::temp_dir();
+    let input = dir.join(gen_bad_file());
+    let output = dir.join(gen_bad_file());
+    fs::write(&input, vec![b'x'; 64 * 1024])?;
+    let result = std::process::Command::new("sh")
+        .args(["-c", "trap '' XFSZ; ulimit -f 8; exec \"$0\" \"$1\" \"$2\" > \"$3\""])
+        .arg(assert_cmd::cargo::cargo_bin(PRG))
+        .args([&input, std::path::Path::new(FOX), &output])
+        .output();
+    fs::remove_file(&input)?;
+    let _ = fs::remove_file(&output);
+    let result = result?;
+    assert!(!result.status.success());
+    assert_eq!(String::from_utf8(result.stderr)?, "catr: File too large (os error 27)\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+fn run(args: &[&str], expected_file: &str) -> TestResult {
+    let expected = fs::read(expected_file)?;
+    Command::cargo_bin(PRG)?
+        .args(args)
+        .assert()
+        .success()
+        .stdout(expected);
+    Ok(())
+}
+
+// --------------------------------------------------
+fn run_stdin(
+    input_file: &str,
+    args: &[&str],
+    expected_file: &str,
+) -> TestResult {
+    let input = fs::read(input_file)?;
+    let expected = fs::read(expected_file)?;
+    Command::cargo_bin(PRG)?
+        .args(args)
+        .write_stdin(input)
+        .assert()
+        .success()
+        .stdout(expected);
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn bustle_stdin() -> TestResult {
+    run_stdin(BUSTLE, &["-"], "tests/expected/the-bustle.txt.stdin.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn bustle_stdin_n() -> TestResult {
+    run_stdin(
+        BUSTLE,
+        &["-n", "-"],
+        "tests/expected/the-bustle.txt.n.stdin.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn bustle_stdin_b() -> TestResult {
+    run_stdin(
+        BUSTLE,
+        &["-b", "-"],
+        "tests/expected/the-bustle.txt.b.stdin.out",
+    )
+}
+
+// --------------------------------------------------
+// Feeds `input` to catr and returns the first line it prints while stdin
+// is still open, or fails if none comes within ten seconds.
+fn first_line_while_open(args: &[&str], input: &[u8]) -> Result<String, Box<dyn Error>> {
+    let mut child = std::process::Command::new(assert_cmd::cargo::cargo_bin(PRG))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()?;
+    child.stdin.as_mut().unwrap().write_all(input)?;
+    let mut stdout = BufReader::new(child.stdout.take().unwrap());
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || {
+        let mut line = String::new();
+        let _ = stdout.read_line(&mut line);
+        let _ = tx.send(line);
+    });
+    let line = rx.recv_timeout(Duration::from_secs(10));
+    child.kill()?;
+    child.wait()?;
+    Ok(line?)
+}
+
+// --------------------------------------------------
+#[test]
+fn stdin_n_streams() -> TestResult {
+    let line = first_line_while_open(&["-n", "--no-decompress"], b"hi\nthe")?;
+    assert_eq!(line, "     1\thi\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn stdin_streams_past_magic() -> TestResult {
+    // "hi" can't begin a magic number, so there is no need to wait for
+    // the six bytes xz's takes, with or without formatting.
+    assert_eq!(first_line_while_open(&[], b"hi\n")?, "hi\n");
+    assert_eq!(first_line_while_open(&["-n"], b"hi\n")?, "     1\thi\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn empty() -> TestResult {
+    run(&[EMPTY], "tests/expected/empty.txt.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn empty_n() -> TestResult {
+    run(&["-n", EMPTY], "tests/expected/empty.txt.n.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn empty_b() -> TestResult {
+    run(&["-b", EMPTY], "tests/expected/empty.txt.b.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn fox() -> TestResult {
+    run(&[FOX], "tests/expected/fox.txt.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn fox_n() -> TestResult {
+    run(&["-n", FOX], "tests/expected/fox.txt.n.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn fox_b() -> TestResult {
+    run(&["-b", FOX], "tests/expected/fox.txt.b.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn spiders() -> TestResult {
+    run(&[SPIDERS], "tests/expected/spiders.txt.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn spiders_n() -> TestResult {
+    run(&["--number", SPIDERS], "tests/expected/spiders.txt.n.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn spiders_b() -> TestResult {
+    run(
+        &["--number-nonblank", SPIDERS],
+        "tests/expected/spiders.txt.b.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn bustle() -> TestResult {
+    run(&[BUSTLE], "tests/expected/the-bustle.txt.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn bustle_n() -> TestResult {
+    run(&["-n", BUSTLE], "tests/expected/the-bustle.txt.n.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn bustle_b() -> TestResult {
+    run(&["-b", BUSTLE], "tests/expected/the-bustle.txt.b.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn no_newline() -> TestResult {
+    run(&[NO_NEWLINE], "tests/expected/no-newline.txt.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn no_newline_n() -> TestResult {
+    run(&["-n", NO_NEWLINE], "tests/expected/no-newline.txt.n.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn latin1() -> TestResult {
+    run(&[LATIN1], "tests/expected/latin1.txt.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn latin1_b() -> TestResult {
+    run(&["-b", LATIN1], "tests/expected/latin1.txt.b.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn all() -> TestResult {
+    run(&[EMPTY, FOX, SPIDERS, BUSTLE], "tests/expected/all.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn all_n() -> TestResult {
+    run(&[EMPTY, FOX, SPIDERS, BUSTLE, "-n"], "tests/expected/all.n.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn all_b() -> TestResult {
+    run(&[EMPTY, FOX, SPIDERS, BUSTLE, "-b"], "tests/expected/all.b.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn joined_n() -> TestResult {
+    run(&["-n", NO_NEWLINE, FOX], "tests/expected/joined.n.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn control_v() -> TestResult {
+    run(&["-v", CONTROL], "tests/expected/control.txt.v.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn control_show_ends() -> TestResult {
+    run(&["--show-ends", CONTROL], "tests/expected/control.txt.E.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn control_show_tabs() -> TestResult {
+    run(&["-T", CONTROL], "tests/expected/control.txt.T.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn control_show_all() -> TestResult {
+    run(&["-A", CONTROL], "tests/expected/control.txt.A.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn control_e() -> TestResult {
+    run(&["-e", CONTROL], "tests/expected/control.txt.e.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn control_t() -> TestResult {
+    run(&["-t", CONTROL], "tests/expected/control.txt.t.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn control_vn() -> TestResult {
+    run(&["-v", "-n", CONTROL], "tests/expected/control.txt.vn.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn control_ab() -> TestResult {
+    run(&["-A", "-b", CONTROL], "tests/expected/control.txt.Ab.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_bad_number_start() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["-n", "--number-start", "x", FOX])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("illegal number start -- x"));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_number_overflow() -> TestResult {
+    // The largest number is still printed; the line after it has none.
+    Command::cargo_bin(PRG)?
+        .args(["-n", "--number-start", "18446744073709551615", SPIDERS])
+        .assert()
+        .failure()
+        .stdout("18446744073709551615\tDon't worry, spiders,\n")
+        .stderr("catr: line number overflow\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_bad_number_width() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["-n", "--number-width", "0", FOX])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("illegal number width -- 0"));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_too_wide_number_width() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["-n", "--number-width", "100000000000", FOX])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("illegal number width -- 100000000000"));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn blanks_s() -> TestResult {
+    run(&["-s", BLANKS], "tests/expected/blanks.txt.s.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn blanks_sn() -> TestResult {
+    run(&["-s", "-n", BLANKS], "tests/expected/blanks.txt.sn.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn blanks_sb() -> TestResult {
+    run(&["--squeeze-blank", "-b", BLANKS], "tests/expected/blanks.txt.sb.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn blanks_twice_s() -> TestResult {
+    run(&["-s", BLANKS, BLANKS], "tests/expected/blanks.twice.s.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn all_n_per_file() -> TestResult {
+    run(
+        &["-n", "--number-per-file", FOX, SPIDERS, BUSTLE],
+        "tests/expected/all.n.per-file.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn spiders_n_start_width() -> TestResult {
+    run(
+        &["-n", "--number-start", "100", "--number-width", "4", SPIDERS],
+        "tests/expected/spiders.txt.n100w4.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn bustle_gz() -> TestResult {
+    run(&[GZ], "tests/expected/the-bustle.txt.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn bustle_bz2() -> TestResult {
+    run(&[BZ2], "tests/expected/the-bustle.txt.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn bustle_xz() -> TestResult {
+    run(&[XZ], "tests/expected/the-bustle.txt.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn bustle_zst_n() -> TestResult {
+    run(&["-n", ZST], "tests/expected/the-bustle.txt.n.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn bustle_gz_misnamed() -> TestResult {
+    run(&["tests/inputs/bustle-gz.bin"], "tests/expected/the-bustle.txt.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn bustle_gz_stdin() -> TestResult {
+    run_stdin(GZ, &["-"], "tests/expected/the-bustle.txt.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn multi_member_gz() -> TestResult {
+    run(&["tests/inputs/multi.gz"], "tests/expected/multi.gz.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn mixed_compressed_and_plain() -> TestResult {
+    run(&[FOX, GZ], "tests/expected/fox-bustle.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn no_decompress() -> TestResult {
+    run(&["--no-decompress", XZ], XZ)
+}
+
+// --------------------------------------------------
+#[test]
+fn reports_corrupt_gz() -> TestResult {
+    let input = fs::read(GZ)?;
+    Command::cargo_bin(PRG)?
+        .arg("-")
+        .write_stdin(&input[..input.len() / 2])
+        .assert()
+        .failure()
+        .stderr(predicate::str::starts_with("catr: -: "));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn control_hex() -> TestResult {
+    run(&["-x", CONTROL], "tests/expected/control.txt.x.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn control_hex_group_cols() -> TestResult {
+    run(
+        &["-x", "--group-size", "4", "--cols", "6", CONTROL],
+        "tests/expected/control.txt.x.g4c6.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn fox_hex_range() -> TestResult {
+    run(
+        &["--hex", "--group-size", "3", "--seek", "5", "--length", "20", FOX],
+        "tests/expected/fox.txt.x.g3s5l20.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn hex_multiple_files() -> TestResult {
+    run(&["-x", FOX, GZ], "tests/expected/fox-bustle.x.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn hex_stdin() -> TestResult {
+    run_stdin(CONTROL, &["-x"], "tests/expected/control.txt.x.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_hex_and_number() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["-x", "-n", FOX])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("cannot be used with"));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_bad_cols() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["-x", "--cols", "0", FOX])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("illegal column count -- 0"));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_too_many_cols() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["-x", "--cols", "100000000000000000", FOX])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("illegal column count -- 100000000000000000"));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn auto_encoding_utf16le() -> TestResult {
+    run(
+        &["--auto-encoding", "tests/inputs/bustle-utf16le.txt"],
+        "tests/expected/bustle-utf16le.txt.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn auto_encoding_utf16le_eol() -> TestResult {
+    run(
+        &[
+            "--auto-encoding",
+            "--normalize-eol",
+            "tests/inputs/bustle-utf16le.txt",
+        ],
+        "tests/expected/bustle-utf16le.txt.eol.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn auto_encoding_utf16be() -> TestResult {
+    run(
+        &["--auto-encoding", "tests/inputs/bustle-utf16be.txt"],
+        "tests/expected/the-bustle.txt.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn auto_encoding_utf8_bom() -> TestResult {
+    run(
+        &["--auto-encoding", "tests/inputs/bustle-utf8bom.txt"],
+        "tests/expected/the-bustle.txt.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn auto_encoding_without_bom() -> TestResult {
+    run(&["--auto-encoding", LATIN1], "tests/expected/latin1.txt.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn from_latin1_eol_n() -> TestResult {
+    run(
+        &[
+            "--from-encoding",
+            "latin1",
+            "--normalize-eol",
+            "-n",
+            "tests/inputs/latin1-crlf.txt",
+        ],
+        "tests/expected/latin1-crlf.txt.eol.n.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn from_latin1_eol_stdin() -> TestResult {
+    run_stdin(
+        "tests/inputs/latin1-crlf.txt",
+        &["--from-encoding", "iso-8859-1", "--normalize-eol"],
+        "tests/expected/latin1-crlf.txt.eol.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_bad_encoding() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["--from-encoding", "klingon", FOX])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("unknown encoding -- klingon"));
+    Ok(())
+}
@@ -0,0 +1,69 @@
+#!/usr/bin/env bash
+
+INPUTS="./tests/inputs"
+OUT_DIR="./tests/expected"
+
+[[ ! -d "$OUT_DIR" ]] && mkdir -p "$OUT_DIR"
+
+for FILE in $INPUTS/*.txt; do
+    BASENAME=$(basename "$FILE")
+    head      $FILE > ${OUT_DIR}/${BASENAME}.out
+    head -n 2 $FILE > ${OUT_DIR}/${BASENAME}.n2.out
+    head -n 4 $FILE > ${OUT_DIR}/${BASENAME}.n4.out
+    head -c 1 $FILE > ${OUT_DIR}/${BASENAME}.c1.out
+    head -c 2 $FILE > ${OUT_DIR}/${BASENAME}.c2.out
+    head -c 4 $FILE > ${OUT_DIR}/${BASENAME}.c4.out
+done
+
+ALL="$INPUTS/empty.txt $INPUTS/one.txt $INPUTS/two.txt $INPUTS/three.txt \
+    $INPUTS/ten.txt"
+head      $ALL > $OUT_DIR/all.out
+head -n 2 $ALL > $OUT_DIR/all.n2.out
+head -n 4 $ALL > $OUT_DIR/all.n4.out
+head -c 1 $ALL > $OUT_DIR/all.c1.out
+head -c 2 $ALL > $OUT_DIR/all.c2.out
+head -c 4 $ALL > $OUT_DIR/all.c4.out
+
+# Negative counts: all but the last K lines or bytes
+for FILE in $INPUTS/*.txt; do
+    BASENAME=$(basename "$FILE")
+    head -n -2 $FILE > ${OUT_DIR}/${BASENAME}.n-2.out
+    head -c -3 $FILE > ${OUT_DIR}/${BASENAME}.c-3.out
+done
+head -n -2 $ALL > $OUT_DIR/all.n-2.out
+head -c -3 $ALL > $OUT_DIR/all.c-3.out
+
+# stdin and unreadable files among regular ones
+head -n 2 $INPUTS/one.txt - $INPUTS/ten.txt < $INPUTS/two.txt \
+    > $OUT_DIR/stdin_between_files.out
+head $INPUTS/empty.txt $INPUTS/one.txt > $OUT_DIR/skips_bad_file.out
+head $INPUTS/no-such-file $INPUTS/one.txt $INPUTS/two.txt \
+    > $OUT_DIR/skips_bad_file_first.out 2> /dev/null
+
+# NUL- and custom-separated records
+NUL="$INPUTS/nul.bin"
+SEMI="$INPUTS/semicolon.bin"
+head -z -n 2  $NUL > $OUT_DIR/nul.bin.z.n2.out
+head -z -n -2 $NUL > $OUT_DIR/nul.bin.z.n-2.out
+head -z       $NUL > $OUT_DIR/nul.bin.z.out
+tr ';\n' '\n;' < $SEMI | head -n 3 | tr ';\n' '\n;' > $OUT_DIR/semicolon.bin.n3.out
+
+# --until / --while, emulated with sed and awk
+MAIL="$INPUTS/mail.log"
+COMMENTS="$INPUTS/comments.log"
+sed '/^$/Q' $MAIL > $OUT_DIR/mail.log.until-blank.out
+sed '/ERROR/q' $MAIL > $OUT_DIR/mail.log.until-error-inclusive.out
+awk '!/^#/ { exit } { print }' $COMMENTS > $OUT_DIR/comments.log.while.out
+(echo "==> $MAIL <=="; sed '/^code/Q' $MAIL; echo; \
+    echo "==> $COMMENTS <=="; sed '/^code/Q' $COMMENTS) > $OUT_DIR/all.until-code.out
+
+# --range and header control
+sed -n '3,5p' $INPUTS/ten.txt > $OUT_DIR/ten.txt.range3-5.out
+sed -n '8,$p' $INPUTS/ten.txt > $OUT_DIR/ten.txt.range8-.out
+head -q -n 2 $ALL > $OUT_DIR/all.q.n2.out
+head -v -n 2 $INPUTS/one.txt > $OUT_DIR/one.txt.v.n2.out
+(for FILE in $INPUTS/two.txt $INPUTS/ten.txt; do
+    [[ $FILE != $INPUTS/two.txt ]] && echo
+    echo "==> $FILE <=="
+    sed -n '2,3p' $FILE
+done) > $OUT_DIR/all.range2-3.out
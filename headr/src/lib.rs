@@ -1,15 +1,52 @@
 use clap::{App, Arg};
+use regex::bytes::Regex;
+use std::collections::VecDeque;
 use std::error::Error;
 use std::fs::File;
-use std::io::{BufReader, BufRead, Read};
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Write};
 
 pub type MyResult<T> = Result<T, Box<dyn Error>>;
 
 #[derive(Debug)]
 pub struct Config {
     pub files: Vec<String>,
-    pub line: usize,
-    pub bytes: Option<usize>,
+    pub line: Count,
+    pub bytes: Option<Count>,
+    /// Byte that ends a line: `\n`, NUL for `-z`, or `--separator`.
+    pub separator: u8,
+    /// `--until`/`--while`: stop at a line instead of after a count.
+    pub stop: Option<Stop>,
+    /// `--range START:END`: print only these lines, counted from 1.
+    pub range: Option<LineRange>,
+    /// `-q`: never print headers.
+    pub quiet: bool,
+    /// `-v`: always print headers.
+    pub verbose: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LineRange {
+    pub start: u64,
+    /// `None` runs to the end of the file.
+    pub end: Option<u64>,
+}
+
+#[derive(Debug)]
+pub enum Stop {
+    /// Print lines up to the first one matching, and that line too when
+    /// `inclusive` is set.
+    Until { pattern: Regex, inclusive: bool },
+    /// Print lines for as long as they match.
+    While(Regex),
+}
+
+/// How much of each file to print, as given to `-n` or `-c`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Count {
+    /// `K`: the first K lines or bytes.
+    First(u64),
+    /// `-K`: everything except the last K lines or bytes.
+    AllBut(u64),
 }
 
 pub fn get_flags() -> MyResult<Config> {
@@ -17,17 +54,19 @@ pub fn get_flags() -> MyResult<Config> {
         .version("0.1.0")
         .arg(
             Arg::with_name("lines")
-                .short("n")    
+                .short("n")
                 .long("lines")
                 .value_name("LINES")
+                .allow_hyphen_values(true)
                 .default_value("10"),
         )
         .arg(
             Arg::with_name("bytes")
-                .short("c")    
+                .short("c")
                 .long("bytes")
                 .value_name("BYTES")
                 .takes_value(true)
+                .allow_hyphen_values(true)
                 .conflicts_with("lines"),
         )
         .arg(
@@ -36,63 +75,401 @@ pub fn get_flags() -> MyResult<Config> {
                 .multiple(true)
                 .default_value("-"),
         )
+        .arg(
+            Arg::with_name("zero_terminated")
+                .short("z")
+                .long("zero-terminated")
+                .conflicts_with("separator"),
+        )
+        .arg(
+            Arg::with_name("separator")
+                .long("separator")
+                .value_name("BYTE")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("until")
+                .long("until")
+                .value_name("PATTERN")
+                .takes_value(true)
+                .conflicts_with_all(&["lines", "bytes", "while"]),
+        )
+        .arg(
+            Arg::with_name("inclusive")
+                .long("inclusive")
+                .requires("until"),
+        )
+        .arg(
+            Arg::with_name("while")
+                .long("while")
+                .value_name("PATTERN")
+                .takes_value(true)
+                .conflicts_with_all(&["lines", "bytes"]),
+        )
+        .arg(
+            Arg::with_name("range")
+                .long("range")
+                .value_name("START:END")
+                .takes_value(true)
+                .conflicts_with_all(&["lines", "bytes", "until", "while"]),
+        )
+        .arg(
+            Arg::with_name("quiet")
+                .short("q")
+                .long("quiet")
+                .alias("silent")
+                .overrides_with("verbose"),
+        )
+        .arg(
+            Arg::with_name("verbose")
+                .short("v")
+                .long("verbose")
+                .overrides_with("quiet"),
+        )
         .get_matches();
 
     let lines = matches.value_of("lines")
-        .map(parse_positive_int)
+        .map(|s| parse_count(s).map_err(|e| format!("illegal line count -- {}: {}", s, e)))
         .transpose()?;
 
     let bytes = matches.value_of("bytes")
-        .map(parse_positive_int)
+        .map(|s| parse_count(s).map_err(|e| format!("illegal byte count -- {}: {}", s, e)))
+        .transpose()?;
+
+    let separator = match matches.value_of("separator") {
+        Some(s) => parse_separator(s)
+            .ok_or_else(|| format!("illegal separator -- {}", s))?,
+        None if matches.is_present("zero_terminated") => b'\0',
+        None => b'\n',
+    };
+
+    let parse_regex = |s: &str| {
+        Regex::new(s).map_err(|e| format!("illegal pattern -- {}: {}", s, e))
+    };
+    let stop = if let Some(s) = matches.value_of("until") {
+        Some(Stop::Until {
+            pattern: parse_regex(s)?,
+            inclusive: matches.is_present("inclusive"),
+        })
+    } else if let Some(s) = matches.value_of("while") {
+        Some(Stop::While(parse_regex(s)?))
+    } else {
+        None
+    };
+
+    let range = matches.value_of("range")
+        .map(|s| parse_range(s).ok_or_else(|| format!("illegal range -- {}", s)))
         .transpose()?;
 
     Ok(Config {
         files: matches.values_of_lossy("files").unwrap(),
         line: lines.unwrap(),
-        bytes: bytes,
+        bytes,
+        separator,
+        stop,
+        range,
+        quiet: matches.is_present("quiet"),
+        verbose: matches.is_present("verbose"),
     })
 }
 
-fn parse_positive_int(s: &str) -> MyResult<usize> {
-    match s.parse() {
-        Ok(n) if n > 0 => Ok(n),
-        _ => Err(From::from(s)),
+// A single byte, given as itself or as `\0`, `\t`, `\n`, `\r` or `\xHH`.
+fn parse_separator(s: &str) -> Option<u8> {
+    match s.as_bytes() {
+        [b] => Some(*b),
+        b"\\0" => Some(b'\0'),
+        b"\\t" => Some(b'\t'),
+        b"\\n" => Some(b'\n'),
+        b"\\r" => Some(b'\r'),
+        [b'\\', b'x', _, _] => u8::from_str_radix(&s[2..], 16).ok(),
+        _ => None,
     }
 }
 
-fn open(filename: &str) -> std::io::Result<File> {
-    let file = File::open(filename)?;
-    Ok(file)
+// `START:END`, either side may be left out: `5:`, `:20`.
+fn parse_range(s: &str) -> Option<LineRange> {
+    let (start, end) = s.split_once(':')?;
+    let start = if start.is_empty() { 1 } else { parse_size(start).ok()? };
+    let end = if end.is_empty() { None } else { Some(parse_size(end).ok()?) };
+    match end {
+        _ if start == 0 => None,
+        Some(end) if end < start => None,
+        _ => Some(LineRange { start, end }),
+    }
 }
 
-pub fn run(config: Config) -> MyResult<()> {
-    let file_num = config.files.len();
+fn parse_count(s: &str) -> MyResult<Count> {
+    match s.strip_prefix('-') {
+        Some(n) => Ok(Count::AllBut(parse_size(n)?)),
+        None => Ok(Count::First(parse_size(s)?)),
+    }
+}
 
-    for filename in config.files {
-        match open(&filename) {
-            Err(_) => (),
-            Ok(file) => {
-                if file_num > 1 {
-                    println!("==> {} <==", filename);
-                }
-                match config.bytes {
-                    Some(n) => {
-                        let mut handle = file.take(n as u64);
-                        let mut buffer = vec![0; n];
-                        let bytes_read = handle.read(&mut buffer)?;
-                        println!("{}", String::from_utf8_lossy(&buffer[..bytes_read]));
-                        println!("");
-                    },
-                    None => {
-                        let f = BufReader::new(file);
-                        for line in f.lines().take(config.line) {
-                            println!("{}", line.unwrap());
-                        }
-                        println!("");
+/// Parses a count with an optional GNU multiplier suffix: `b` for 512,
+/// `K` or `KiB` for 1024, `KB` for 1000, and likewise `M`, `G`, `T`, `P`
+/// and `E`. Counts that don't fit in a `u64` are an error.
+pub fn parse_size(s: &str) -> MyResult<u64> {
+    let split = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    let (digits, suffix) = s.split_at(split);
+    if digits.is_empty() {
+        return Err(From::from("invalid number"));
+    }
+    let multiplier: u64 = match suffix {
+        "" => 1,
+        "b" => 512,
+        _ => {
+            let mut chars = suffix.chars();
+            let power = match chars.next() {
+                Some('k' | 'K') => 1,
+                Some('M') => 2,
+                Some('G') => 3,
+                Some('T') => 4,
+                Some('P') => 5,
+                Some('E') => 6,
+                Some('Z') => 7,
+                Some('Y') => 8,
+                _ => return Err(From::from("invalid suffix")),
+            };
+            let base: u64 = match chars.as_str() {
+                "" | "iB" => 1024,
+                "B" => 1000,
+                _ => return Err(From::from("invalid suffix")),
+            };
+            base.checked_pow(power).ok_or("value too large")?
+        }
+    };
+    digits
+        .parse::<u64>()
+        .ok()
+        .and_then(|n| n.checked_mul(multiplier))
+        .ok_or_else(|| From::from("value too large"))
+}
+
+fn open(filename: &str) -> io::Result<Box<dyn BufRead>> {
+    match filename {
+        "-" => Ok(Box::new(BufReader::new(io::stdin()))),
+        _ => Ok(Box::new(BufReader::new(File::open(filename)?))),
+    }
+}
+
+// Copies the part of `file` selected by `config` to `out`. Negative counts
+// only ever hold the last K lines or bytes, so memory stays bounded no
+// matter how large the input is.
+fn head<R: BufRead, W: Write>(config: &Config, file: &mut R, out: &mut W) -> io::Result<()> {
+    if let Some(stop) = &config.stop {
+        return head_until(stop, config.separator, file, out);
+    }
+    if let Some(range) = config.range {
+        return head_range(range, config.separator, file, out);
+    }
+    match config.bytes {
+        Some(Count::First(n)) => {
+            io::copy(&mut file.take(n), out)?;
+        }
+        Some(Count::AllBut(k)) => all_but_bytes(k as usize, file, out)?,
+        None => match config.line {
+            Count::First(n) => {
+                let mut line = Vec::new();
+                for _ in 0..n {
+                    line.clear();
+                    if file.read_until(config.separator, &mut line)? == 0 {
+                        break;
                     }
+                    out.write_all(&line)?;
                 }
             }
+            Count::AllBut(k) => all_but_lines(k as usize, config.separator, file, out)?,
+        },
+    }
+    Ok(())
+}
+
+// Patterns are matched against each line without its terminator.
+fn head_until<R: BufRead, W: Write>(
+    stop: &Stop,
+    separator: u8,
+    file: &mut R,
+    out: &mut W,
+) -> io::Result<()> {
+    let mut line = Vec::new();
+    loop {
+        line.clear();
+        if file.read_until(separator, &mut line)? == 0 {
+            break;
+        }
+        let text = line.strip_suffix(&[separator]).unwrap_or(&line);
+        match stop {
+            Stop::Until { pattern, inclusive } if pattern.is_match(text) => {
+                if *inclusive {
+                    out.write_all(&line)?;
+                }
+                break;
+            }
+            Stop::While(pattern) if !pattern.is_match(text) => break,
+            _ => out.write_all(&line)?,
         }
     }
     Ok(())
-}
\ No newline at end of file
+}
+
+// Reading stops as soon as the last line of the range has been written.
+fn head_range<R: BufRead, W: Write>(
+    range: LineRange,
+    separator: u8,
+    file: &mut R,
+    out: &mut W,
+) -> io::Result<()> {
+    let mut line = Vec::new();
+    let mut line_num = 0;
+    while range.end.is_none_or(|end| line_num < end) {
+        line.clear();
+        if file.read_until(separator, &mut line)? == 0 {
+            break;
+        }
+        line_num += 1;
+        if line_num >= range.start {
+            out.write_all(&line)?;
+        }
+    }
+    Ok(())
+}
+
+fn all_but_lines<R: BufRead, W: Write>(
+    k: usize,
+    separator: u8,
+    file: &mut R,
+    out: &mut W,
+) -> io::Result<()> {
+    let mut held: VecDeque<Vec<u8>> = VecDeque::new();
+    loop {
+        let mut line = Vec::new();
+        if file.read_until(separator, &mut line)? == 0 {
+            break;
+        }
+        held.push_back(line);
+        if held.len() > k {
+            out.write_all(&held.pop_front().unwrap())?;
+        }
+    }
+    Ok(())
+}
+
+// The last K bytes are held back in a ring that grows only as far as the
+// input does, so a huge K on a small file costs no more than the file.
+fn all_but_bytes<R: BufRead, W: Write>(k: usize, file: &mut R, out: &mut W) -> io::Result<()> {
+    let mut held: VecDeque<u8> = VecDeque::new();
+    loop {
+        let buf = match file.fill_buf() {
+            Ok(buf) => buf,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+        if buf.is_empty() {
+            break;
+        }
+        let len = buf.len();
+        held.extend(buf);
+        file.consume(len);
+        if held.len() > k {
+            let excess = held.len() - k;
+            let (front, back) = held.as_slices();
+            let split = excess.min(front.len());
+            out.write_all(&front[..split])?;
+            out.write_all(&back[..excess - split])?;
+            held.drain(..excess);
+        }
+    }
+    Ok(())
+}
+
+/// Returns `Ok(false)` when at least one file could not be read; the
+/// error has already been reported on stderr.
+pub fn run(config: Config) -> MyResult<bool> {
+    let file_num = config.files.len();
+    let stdout = io::stdout();
+    let mut out = BufWriter::new(stdout.lock());
+    let mut all_ok = true;
+    let mut first_header = true;
+
+    for filename in &config.files {
+        match open(filename) {
+            Err(e) => {
+                out.flush()?;
+                eprintln!("headr: {}: {}", filename, e);
+                all_ok = false;
+            }
+            Ok(mut file) => {
+                // Like GNU head, a header is printed for every file that
+                // opens, with a blank line before all but the first.
+                if config.verbose || (file_num > 1 && !config.quiet) {
+                    let name = if filename == "-" { "standard input" } else { filename };
+                    let sep = if first_header { "" } else { "\n" };
+                    writeln!(out, "{}==> {} <==", sep, name)?;
+                    first_header = false;
+                }
+                if let Err(e) = head(&config, &mut file, &mut out) {
+                    out.flush()?;
+                    eprintln!("headr: {}: {}", filename, e);
+                    all_ok = false;
+                }
+            }
+        }
+    }
+    out.flush()?;
+    Ok(all_ok)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{parse_range, parse_separator, parse_size, LineRange};
+
+    #[test]
+    fn test_parse_separator() {
+        assert_eq!(parse_separator(";"), Some(b';'));
+        assert_eq!(parse_separator("\\0"), Some(0));
+        assert_eq!(parse_separator("\\t"), Some(b'\t'));
+        assert_eq!(parse_separator("\\x1e"), Some(0x1e));
+        assert_eq!(parse_separator(""), None);
+        assert_eq!(parse_separator("ab"), None);
+        assert_eq!(parse_separator("\\x1ff"), None);
+    }
+
+    #[test]
+    fn test_parse_range() {
+        let range = |start, end| Some(LineRange { start, end });
+        assert_eq!(parse_range("5:10"), range(5, Some(10)));
+        assert_eq!(parse_range("5:5"), range(5, Some(5)));
+        assert_eq!(parse_range("5:"), range(5, None));
+        assert_eq!(parse_range(":20"), range(1, Some(20)));
+        assert_eq!(parse_range("1K:2K"), range(1024, Some(2048)));
+        assert_eq!(parse_range("5"), None);
+        assert_eq!(parse_range("0:3"), None);
+        assert_eq!(parse_range("10:5"), None);
+        assert_eq!(parse_range("a:b"), None);
+    }
+
+    #[test]
+    fn test_parse_size() {
+        assert_eq!(parse_size("0").unwrap(), 0);
+        assert_eq!(parse_size("42").unwrap(), 42);
+        assert_eq!(parse_size("2b").unwrap(), 1024);
+        assert_eq!(parse_size("1K").unwrap(), 1024);
+        assert_eq!(parse_size("1k").unwrap(), 1024);
+        assert_eq!(parse_size("1KiB").unwrap(), 1024);
+        assert_eq!(parse_size("1KB").unwrap(), 1000);
+        assert_eq!(parse_size("64MiB").unwrap(), 64 << 20);
+        assert_eq!(parse_size("3MB").unwrap(), 3_000_000);
+        assert_eq!(parse_size("1G").unwrap(), 1 << 30);
+        assert_eq!(parse_size("2TB").unwrap(), 2_000_000_000_000);
+        assert_eq!(parse_size("15E").unwrap(), 15 << 60);
+
+        assert!(parse_size("").is_err());
+        assert!(parse_size("K").is_err());
+        assert!(parse_size("1X").is_err());
+        assert!(parse_size("1Ki").is_err());
+        assert!(parse_size("1.5M").is_err());
+        assert!(parse_size("16E").is_err());
+        assert!(parse_size("1Z").is_err());
+        assert!(parse_size("99999999999999999999").is_err());
+    }
+}
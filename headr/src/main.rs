@@ -1,4 +1,10 @@
-fn main() -> headr::MyResult<()> {
-    headr::run(headr::get_flags().unwrap()).unwrap();
-    Ok(())
+fn main() {
+    match headr::get_flags().and_then(headr::run) {
+        Ok(true) => {}
+        Ok(false) => std::process::exit(1),
+        Err(e) => {
+            eprintln!("headr: {}", e);
+            std::process::exit(1);
+        }
+    }
 }
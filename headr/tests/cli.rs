@@ -1,11 +1,7 @@
 use assert_cmd::Command;
 use predicates::prelude::*;
 use rand::{distributions::Alphanumeric, Rng};
-use std::io::prelude::*;
-use std::{
-    error::Error,
-    fs::{self, File},
-};
+use std::{error::Error, fs};
 
 type TestResult = Result<(), Box<dyn Error>>;
 
@@ -15,6 +11,10 @@ const ONE: &str = "./tests/inputs/one.txt";
 const TWO: &str = "./tests/inputs/two.txt";
 const THREE: &str = "./tests/inputs/three.txt";
 const TEN: &str = "./tests/inputs/ten.txt";
+const NUL: &str = "./tests/inputs/nul.bin";
+const SEMI: &str = "./tests/inputs/semicolon.bin";
+const MAIL: &str = "./tests/inputs/mail.log";
+const COMMENTS: &str = "./tests/inputs/comments.log";
 
 // --------------------------------------------------
 fn random_string() -> String {
@@ -41,7 +41,7 @@ fn dies_bad_bytes() -> TestResult {
     let bad = random_string();
     let expected = format!("illegal byte count -- {}", &bad);
     Command::cargo_bin(PRG)?
-        .args(&["-c", &bad, EMPTY])
+        .args(["-c", &bad, EMPTY])
         .assert()
         .failure()
         .stderr(predicate::str::contains(expected));
@@ -55,7 +55,7 @@ fn dies_bad_lines() -> TestResult {
     let bad = random_string();
     let expected = format!("illegal line count -- {}", &bad);
     Command::cargo_bin(PRG)?
-        .args(&["-n", &bad, EMPTY])
+        .args(["-n", &bad, EMPTY])
         .assert()
         .failure()
         .stderr(predicate::str::contains(expected));
@@ -70,7 +70,7 @@ fn dies_bytes_and_lines() -> TestResult {
                used with '--bytes <BYTES>'";
 
     Command::cargo_bin(PRG)?
-        .args(&["-n", "1", "-c", "2"])
+        .args(["-n", "1", "-c", "2"])
         .assert()
         .failure()
         .stderr(predicate::str::contains(msg));
@@ -82,28 +82,39 @@ fn dies_bytes_and_lines() -> TestResult {
 #[test]
 fn skips_bad_file() -> TestResult {
     let bad = gen_bad_file();
-    let expected = format!("{}: .* [(]os error 2[)]", bad);
+    let expected = format!("headr: {}: .* [(]os error 2[)]", bad);
     Command::cargo_bin(PRG)?
         .args([EMPTY, &bad, ONE])
         .assert()
+        .failure()
+        .stdout(fs::read("tests/expected/skips_bad_file.out")?)
         .stderr(predicate::str::is_match(expected)?);
 
     Ok(())
 }
 
+// --------------------------------------------------
+#[test]
+fn skips_bad_file_first() -> TestResult {
+    let bad = gen_bad_file();
+    Command::cargo_bin(PRG)?
+        .args([&bad, ONE, TWO])
+        .assert()
+        .failure()
+        .stdout(fs::read("tests/expected/skips_bad_file_first.out")?);
+
+    Ok(())
+}
+
 // --------------------------------------------------
 fn run(args: &[&str], expected_file: &str) -> TestResult {
-    // Extra work here due to lossy UTF
-    let mut file = File::open(expected_file)?;
-    let mut buffer = Vec::new();
-    file.read_to_end(&mut buffer)?;
-    let expected = String::from_utf8_lossy(&buffer);
+    let expected = fs::read(expected_file)?;
 
     Command::cargo_bin(PRG)?
         .args(args)
         .assert()
         .success()
-        .stdout(predicate::eq(&expected.as_bytes() as &[u8]));
+        .stdout(expected);
 
     Ok(())
 }
@@ -114,18 +125,15 @@ fn run_stdin(
     input_file: &str,
     expected_file: &str,
 ) -> TestResult {
-    // Extra work here due to lossy UTF
-    let mut file = File::open(expected_file)?;
-    let mut buffer = Vec::new();
-    file.read_to_end(&mut buffer)?;
-    let expected = String::from_utf8_lossy(&buffer);
-    let input = fs::read_to_string(input_file)?;
+    let expected = fs::read(expected_file)?;
+    let input = fs::read(input_file)?;
 
     Command::cargo_bin(PRG)?
         .write_stdin(input)
         .args(args)
         .assert()
-        .stdout(predicate::eq(&expected.as_bytes() as &[u8]));
+        .success()
+        .stdout(expected);
 
     Ok(())
 }
@@ -419,3 +427,274 @@ fn multiple_files_c4() -> TestResult {
         "tests/expected/all.c4.out",
     )
 }
+
+// --------------------------------------------------
+#[test]
+fn empty_n_minus2() -> TestResult {
+    run(&[EMPTY, "-n", "-2"], "tests/expected/empty.txt.n-2.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn one_n_minus2() -> TestResult {
+    run(&[ONE, "-n", "-2"], "tests/expected/one.txt.n-2.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn ten_n_minus2() -> TestResult {
+    run(&[TEN, "-n", "-2"], "tests/expected/ten.txt.n-2.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn ten_c_minus3() -> TestResult {
+    run(&[TEN, "-c", "-3"], "tests/expected/ten.txt.c-3.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn three_c_minus3() -> TestResult {
+    run(&[THREE, "--bytes=-3"], "tests/expected/three.txt.c-3.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn multiple_files_n_minus2() -> TestResult {
+    run(
+        &[EMPTY, ONE, TWO, THREE, TEN, "-n", "-2"],
+        "tests/expected/all.n-2.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn multiple_files_c_minus3() -> TestResult {
+    run(
+        &["-c", "-3", EMPTY, ONE, TWO, THREE, TEN],
+        "tests/expected/all.c-3.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_bad_negative_lines() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["-n", "--5", EMPTY])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("illegal line count -- --5"));
+
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn stdin_between_files() -> TestResult {
+    run_stdin(
+        &["-n", "2", ONE, "-", TEN],
+        TWO,
+        "tests/expected/stdin_between_files.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn ten_n_suffix() -> TestResult {
+    run(&[TEN, "-n", "1K"], "tests/expected/ten.txt.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn ten_c_block_suffix() -> TestResult {
+    run(&[TEN, "-c", "-1b"], "tests/expected/empty.txt.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_bytes_too_large() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["-c", "20E", TEN])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "illegal byte count -- 20E: value too large",
+        ));
+
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_bad_suffix() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["-n", "3X", TEN])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("illegal line count -- 3X: invalid suffix"));
+
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn nul_z() -> TestResult {
+    run(&["-z", NUL], "tests/expected/nul.bin.z.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn nul_z_n2() -> TestResult {
+    run(&["--zero-terminated", "-n", "2", NUL], "tests/expected/nul.bin.z.n2.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn nul_z_n_minus2() -> TestResult {
+    run(&["-z", "-n", "-2", NUL], "tests/expected/nul.bin.z.n-2.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn nul_separator_escape() -> TestResult {
+    run(&["--separator", "\\0", "-n", "2", NUL], "tests/expected/nul.bin.z.n2.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn semicolon_separator() -> TestResult {
+    run(&["--separator", ";", "-n", "3", SEMI], "tests/expected/semicolon.bin.n3.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_bad_separator() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["--separator", "ab", SEMI])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("illegal separator -- ab"));
+
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn mail_until_blank() -> TestResult {
+    run(&["--until", "^$", MAIL], "tests/expected/mail.log.until-blank.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn mail_until_error_inclusive() -> TestResult {
+    run(
+        &["--until", "ERROR", "--inclusive", MAIL],
+        "tests/expected/mail.log.until-error-inclusive.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn comments_while() -> TestResult {
+    run(&["--while", "^#", COMMENTS], "tests/expected/comments.log.while.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn comments_while_stdin() -> TestResult {
+    run_stdin(&["--while", "^#"], COMMENTS, "tests/expected/comments.log.while.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn multiple_files_until() -> TestResult {
+    run(&["--until", "^code", MAIL, COMMENTS], "tests/expected/all.until-code.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_bad_pattern() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["--until", "(", MAIL])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("illegal pattern -- ("));
+
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_until_and_lines() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["--until", "x", "-n", "2", MAIL])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("cannot be used with"));
+
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn multiple_files_quiet() -> TestResult {
+    run(
+        &["-q", "-n", "2", EMPTY, ONE, TWO, THREE, TEN],
+        "tests/expected/all.q.n2.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn one_verbose() -> TestResult {
+    run(&["--verbose", "-n", "2", ONE], "tests/expected/one.txt.v.n2.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn quiet_then_verbose() -> TestResult {
+    run(&["-q", "-v", "-n", "2", ONE], "tests/expected/one.txt.v.n2.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn ten_range() -> TestResult {
+    run(&["--range", "3:5", TEN], "tests/expected/ten.txt.range3-5.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn ten_range_open_end() -> TestResult {
+    run(&["--range", "8:", TEN], "tests/expected/ten.txt.range8-.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn ten_range_stdin() -> TestResult {
+    run_stdin(&["--range", "3:5"], TEN, "tests/expected/ten.txt.range3-5.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn multiple_files_range() -> TestResult {
+    run(&["--range", "2:3", TWO, TEN], "tests/expected/all.range2-3.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_bad_range() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["--range", "5:2", TEN])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("illegal range -- 5:2"));
+
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn one_c_minus_huge() -> TestResult {
+    run(&[ONE, "-c", "-8G"], "tests/expected/empty.txt.out")?;
+    run(&[ONE, "-c", "-1E"], "tests/expected/empty.txt.out")
+}
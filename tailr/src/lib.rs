@@ -68,16 +68,14 @@ pub fn get_flags() -> MyResult<Config> {
 }
 
 
+/// Sizes take the same suffixes as headr, e.g. `-c 10M` or `-n +2K`.
 pub fn parse_plus_usize(input: &str) -> MyResult<PlusUsize> {
-    match input.parse::<usize>() {
-        Ok(num) => {
-            if input.starts_with('+') {
-                Ok(PlusUsize::PlusUsize(num))
-            } else {
-                Ok(PlusUsize::Usize(num))
-            }
+    match input.strip_prefix('+') {
+        Some(num) => Ok(PlusUsize::PlusUsize(headr::parse_size(num)? as usize)),
+        None => {
+            let num = input.strip_prefix('-').unwrap_or(input);
+            Ok(PlusUsize::Usize(headr::parse_size(num)? as usize))
         },
-        Err(_) => {Err(From::from("parse failed"))},
     }
 }
 
@@ -154,5 +152,13 @@ mod test {
         let a = "-3";
         let b = parse_plus_usize(a);
         assert!(b.map_err(|err| println!("{}", err)).is_ok());
+
+        let a = "10M";
+        let b = parse_plus_usize(a);
+        assert_eq!(b.unwrap(), PlusUsize::Usize(10 << 20));
+
+        let a = "+1KB";
+        let b = parse_plus_usize(a);
+        assert_eq!(b.unwrap(), PlusUsize::PlusUsize(1000));
     }
 }
@@ -0,0 +1,133 @@
+#!/usr/bin/env bash
+
+ROOT="tests/inputs"
+OUT_DIR="tests/expected"
+
+[[ ! -d "$OUT_DIR" ]] && mkdir -p "$OUT_DIR"
+
+# Cf https://github.com/coreutils/coreutils/blob/master/tests/misc/uniq.pl
+echo -ne "a\na\n"    > $ROOT/t1.txt
+echo -ne "a\na"      > $ROOT/t2.txt
+echo -ne "a\nb"      > $ROOT/t3.txt
+echo -ne "a\na\nb"   > $ROOT/t4.txt
+echo -ne "b\na\na\n" > $ROOT/t5.txt
+echo -ne "a\nb\nc\n" > $ROOT/t6.txt
+
+# uniqr -c prints the bare count, where GNU uniq pads it to seven columns.
+unpad() {
+    sed -E 's/^ *([0-9]+) /\1 /'
+}
+
+for FILE in $ROOT/*.txt; do
+    BASENAME=$(basename "$FILE")
+    uniq      $FILE > ${OUT_DIR}/${BASENAME}.out
+    uniq -c   $FILE | unpad > ${OUT_DIR}/${BASENAME}.c.out
+    uniq    < $FILE > ${OUT_DIR}/${BASENAME}.stdin.out
+    uniq -c < $FILE | unpad > ${OUT_DIR}/${BASENAME}.stdin.c.out
+
+    # Every line of each duplicate group, and every line grouped
+    uniq -D                      $FILE > ${OUT_DIR}/${BASENAME}.D.out
+    uniq --all-repeated=prepend  $FILE > ${OUT_DIR}/${BASENAME}.D-prepend.out
+    uniq --all-repeated=separate $FILE > ${OUT_DIR}/${BASENAME}.D-separate.out
+    uniq --group                 $FILE > ${OUT_DIR}/${BASENAME}.group.out
+    uniq --group=prepend         $FILE > ${OUT_DIR}/${BASENAME}.group-prepend.out
+    uniq --group=append          $FILE > ${OUT_DIR}/${BASENAME}.group-append.out
+    uniq --group=both            $FILE > ${OUT_DIR}/${BASENAME}.group-both.out
+done
+
+# Case-insensitive groups
+uniq -D -i               $ROOT/case.txt > $OUT_DIR/case.txt.D.i.out
+uniq --group=append -i   $ROOT/case.txt > $OUT_DIR/case.txt.group-append.i.out
+
+# Skipping blank-separated fields, then characters
+FIELDS="$ROOT/fields.txt"
+uniq -f 1      $FIELDS > $OUT_DIR/fields.txt.f1.out
+uniq -f 2      $FIELDS > $OUT_DIR/fields.txt.f2.out
+uniq -f 1 -i   $FIELDS > $OUT_DIR/fields.txt.f1.i.out
+uniq -f 1 -s 2 $FIELDS > $OUT_DIR/fields.txt.f1.s2.out
+uniq -f 1 -w 2 $FIELDS > $OUT_DIR/fields.txt.f1.w2.out
+uniq -f 9      $FIELDS > $OUT_DIR/fields.txt.f9.out
+
+# GNU uniq has no --key-delimiter, so compare on the tab-separated columns
+# after the first N with awk.
+key_delimiter() {
+    awk -F'\t' -v n="$1" '{
+        key = ""
+        for (i = n + 1; i <= NF; i++) key = key (i > n + 1 ? FS : "") $i
+        if (NR == 1 || key != prev) print
+        prev = key
+    }' "$2"
+}
+key_delimiter 1 $ROOT/log.tsv > $OUT_DIR/log.tsv.d1.out
+key_delimiter 2 $ROOT/log.tsv > $OUT_DIR/log.tsv.d2.out
+
+# Duplicates anywhere in the input, first occurrences kept in order. The
+# tests check that spilling changes nothing on inputs of their own.
+global() {
+    awk -v filter="$1" '{
+        if (!($0 in count)) order[n++] = $0
+        count[$0]++
+    }
+    END {
+        for (i = 0; i < n; i++) {
+            c = count[order[i]]
+            if (filter == "c") printf "%d %s\n", c, order[i]
+            else if (filter == "" || (filter == "d" && c > 1) || (filter == "u" && c == 1))
+                print order[i]
+        }
+    }' "$2"
+}
+for FILE in $ROOT/access.log $ROOT/three.txt; do
+    BASENAME=$(basename "$FILE")
+    global "" $FILE > ${OUT_DIR}/${BASENAME}.global.out
+    global c  $FILE > ${OUT_DIR}/${BASENAME}.global.c.out
+    global d  $FILE > ${OUT_DIR}/${BASENAME}.global.d.out
+    global u  $FILE > ${OUT_DIR}/${BASENAME}.global.u.out
+done
+
+# Frequency report, as from sort | uniq -c | sort -rn. Ties keep the order
+# they come in: lexical from sort, or first appearance from global above.
+ACCESS="$ROOT/access.log"
+TOTAL=$(wc -l < $ACCESS)
+sort $ACCESS | uniq -c | unpad | sort -s -k1,1nr > $OUT_DIR/access.log.sorted.lexical.out
+global c $ACCESS | sort -s -k1,1nr > $OUT_DIR/access.log.sorted.out
+global c $ACCESS | sort -s -k1,1nr | head -n 3 > $OUT_DIR/access.log.sorted.top3.out
+global c $ACCESS | sort -s -k1,1nr | awk '$1 >= 2' > $OUT_DIR/access.log.sorted.min2.out
+global c $ACCESS | sort -s -k1,1nr \
+    | awk -v total=$TOTAL '{ n = $1; sub(/^[0-9]+ /, ""); printf "%d %6.2f%% %s\n", n, 100 * n / total, $0 }' \
+    > $OUT_DIR/access.log.sorted.percent.out
+awk '{
+    key = tolower($0)
+    if (!(key in count)) { order[n++] = key; first[key] = $0 }
+    count[key]++
+}
+END { for (i = 0; i < n; i++) printf "%d %s\n", count[order[i]], first[order[i]] }' $ACCESS \
+    | sort -s -k1,1nr > $OUT_DIR/access.log.sorted.i.out
+
+# --mask, with the masks as sed patterns good enough for app.log. Runs of
+# equal masked lines are then folded as uniq would.
+mask() {
+    sed -E \
+        -e 's/\b[0-9a-f]{8}-[0-9a-f]{4}-[0-9a-f]{4}-[0-9a-f]{4}-[0-9a-f]{12}\b/<uuid>/g' \
+        -e 's/\b[0-9a-f]{1,4}::[0-9a-f]{1,4}\b/<ip>/g' \
+        -e 's/\b[0-9]{1,3}(\.[0-9]{1,3}){3}\b/<ip>/g' \
+        -e 's/\b0x[0-9a-f]+\b/<hex>/g' \
+        -e 's/\b[0-9a-f]*([0-9][a-f]|[a-f][0-9])[0-9a-f]*\b/<hex>/g' \
+        -e 's/[0-9]+/<num>/g' "$1"
+}
+fold_masked() {
+    paste -d '\n' <(mask "$2") "$2" | awk -v flag="$1" '
+    function flush() {
+        if (n == 0 || (flag == "d" && n == 1) || (flag == "u" && n > 1)) return
+        if (flag == "c") printf "%d ", n
+        print first
+    }
+    NR % 2 { key = $0; next }
+    key != last { flush(); last = key; first = $0; n = 0 }
+    { n++ }
+    END { flush() }'
+}
+APP="$ROOT/app.log"
+for FLAG in "" c d u; do
+    fold_masked "$FLAG" $APP > $OUT_DIR/app.log.mask${FLAG:+.$FLAG}.out
+done
@@ -1,8 +1,13 @@
-use std::{error::Error};
-use std::io::{self, BufRead, BufReader, Write};
+use std::{borrow::Cow, error::Error};
+use std::cmp::Reverse;
+use std::collections::{hash_map, BinaryHeap, HashMap};
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::fmt::Debug;
 use std::fs::File;
 
 use clap::{App, Arg};
+use unicode_segmentation::UnicodeSegmentation;
 
 type MyResult<T> = Result<T, Box<dyn Error>>;
 
@@ -13,38 +18,333 @@ pub struct Config {
     count: bool,
     repeated: bool,
     ignore: bool,
+    skip_fields: Option<usize>,
+    key_delimiter: Option<char>,
     skip_chars: Option<usize>,
     unique: bool,
     check_chars: Option<usize>,
+    graphemes: bool,
+    all_repeated: Option<Delimit>,
+    group: Option<Group>,
+    global: bool,
+    memory_limit: usize,
+    count_sorted: Option<CountSorted>,
+    mask: bool,
+    comparator: Box<dyn Comparator>,
+}
+
+// Settings for --count-sorted, which reports every distinct line with its
+// count, most frequent first.
+#[derive(Debug)]
+struct CountSorted {
+    top: Option<usize>,
+    min_count: u64,
+    percent: bool,
+    // Ties go to the line seen first, or with --tie-break=lexical to the
+    // line that sorts first.
+    lexical: bool,
+}
+
+// How -D marks off the duplicate groups it prints.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Delimit {
+    None,
+    Prepend,
+    Separate,
+}
+
+// Where --group puts blank lines: between groups, and also before the
+// first or after the last.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Group {
+    Separate,
+    Prepend,
+    Append,
+    Both,
 }
 
 pub trait ConditionalEq {
     type Other;
-    fn eq(&self, other: &Self::Other, config: &Config) -> bool;
+    fn eq(&self, other: &Self::Other, config: &Config) -> bool {
+        self.eq_by(other, config, config.comparator.as_ref())
+    }
+    fn eq_by(&self, other: &Self::Other, config: &Config, comparator: &dyn Comparator) -> bool;
+}
+
+impl ConditionalEq for Vec<u8> {
+    type Other = Vec<u8>;
+
+    fn eq_by(&self, other: &Self::Other, config: &Config, comparator: &dyn Comparator) -> bool {
+        comparator.same(&key(self, config), &key(other, config))
+    }
 }
 
 impl ConditionalEq for String {
     type Other = String;
 
-    fn eq(&self, other: &Self::Other, config: &Config) -> bool {
-        let mut this_string = String::from(self);
-        let mut other_string = String::from(other);
+    fn eq_by(&self, other: &Self::Other, config: &Config, comparator: &dyn Comparator) -> bool {
+        comparator.same(&key(self.as_bytes(), config), &key(other.as_bytes(), config))
+    }
+}
+
+// Decides whether two keys, as made by key(), belong to the same group.
+// Only Exact is an equivalence, so --global and --count-sorted, which hash
+// keys, always use it.
+pub trait Comparator: Debug {
+    fn same(&self, a: &[u8], b: &[u8]) -> bool;
+}
+
+#[derive(Debug)]
+pub struct Exact;
+
+impl Comparator for Exact {
+    fn same(&self, a: &[u8], b: &[u8]) -> bool {
+        a == b
+    }
+}
+
+// Keys are alike when their edit distance in characters, divided by the
+// length of the longer one, is under the threshold.
+#[derive(Debug)]
+pub struct Similarity {
+    threshold: f64,
+}
+
+impl Similarity {
+    pub fn new(threshold: f64) -> Self {
+        Similarity { threshold }
+    }
+}
+
+impl Comparator for Similarity {
+    fn same(&self, a: &[u8], b: &[u8]) -> bool {
+        if a == b {
+            return true;
+        }
+        let a: Vec<char> = String::from_utf8_lossy(a).chars().collect();
+        let b: Vec<char> = String::from_utf8_lossy(b).chars().collect();
+        let distance = edit_distance(&a, &b);
+        (distance as f64) / (a.len().max(b.len()) as f64) < self.threshold
+    }
+}
+
+// Levenshtein distance, keeping one row of the table at a time.
+fn edit_distance(a: &[char], b: &[char]) -> usize {
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, x) in a.iter().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, y) in b.iter().enumerate() {
+            let substitute = diagonal + (x != y) as usize;
+            diagonal = row[j + 1];
+            row[j + 1] = substitute.min(row[j] + 1).min(diagonal + 1);
+        }
+    }
+    row[b.len()]
+}
+
+// The part of a line that is compared. Fields are skipped first, then -s
+// characters, and -w counts from there, as in GNU uniq. Characters are
+// Unicode scalar values, or grapheme clusters with --graphemes, and every
+// byte of invalid UTF-8 counts as one.
+fn key<'a>(line: &'a [u8], config: &Config) -> Cow<'a, [u8]> {
+    let mut key = skip_fields(line, config);
+    if let Some(n) = config.skip_chars {
+        key = &key[advance(key, n, config.graphemes)..];
+    }
+    if let Some(n) = config.check_chars {
+        key = &key[..advance(key, n, config.graphemes)];
+    }
+    let key = if config.mask { Cow::Owned(mask(key)) } else { Cow::Borrowed(key) };
+    if config.ignore {
+        Cow::Owned(fold_case(&key))
+    } else {
+        key
+    }
+}
 
-        if let Some(n) = config.check_chars {
-            this_string.replace_range(if n < this_string.len() {n} else {this_string.len()}.., "");
-            other_string.replace_range(if n < other_string.len() {n} else {other_string.len()}.., "");
+// A field is a run of blanks and then a run of non-blanks, so leading
+// blanks belong to the field. With --key-delimiter every delimiter ends a
+// field instead, empty fields included.
+fn skip_fields<'a>(line: &'a [u8], config: &Config) -> &'a [u8] {
+    let n = match config.skip_fields {
+        Some(n) => n,
+        None => return line,
+    };
+    let mut rest = line;
+    match config.key_delimiter {
+        Some(delim) => {
+            let mut buf = [0; 4];
+            let delim = delim.encode_utf8(&mut buf).as_bytes();
+            for _ in 0..n {
+                match rest.windows(delim.len()).position(|w| w == delim) {
+                    Some(i) => rest = &rest[i + delim.len()..],
+                    None => return &[],
+                }
+            }
+        }
+        None => {
+            let is_blank = |b: &u8| *b == b' ' || *b == b'\t';
+            for _ in 0..n {
+                let start = rest.iter().position(|b| !is_blank(b)).unwrap_or(rest.len());
+                rest = &rest[start..];
+                let end = rest.iter().position(is_blank).unwrap_or(rest.len());
+                rest = &rest[end..];
+            }
+        }
+    }
+    rest
+}
+
+// The byte offset just past the first n characters or graphemes.
+fn advance(bytes: &[u8], n: usize, graphemes: bool) -> usize {
+    let mut left = n;
+    let mut offset = 0;
+    for chunk in bytes.utf8_chunks() {
+        let valid = chunk.valid();
+        let start = if graphemes {
+            valid.grapheme_indices(true).map(|(i, _)| i).nth(left)
+        } else {
+            valid.char_indices().map(|(i, _)| i).nth(left)
+        };
+        if let Some(i) = start {
+            return offset + i;
+        }
+        left -= if graphemes { valid.graphemes(true).count() } else { valid.chars().count() };
+        offset += valid.len();
+
+        let invalid = chunk.invalid().len();
+        if left < invalid {
+            return offset + left;
         }
-    
-        if let Some(n) = config.skip_chars {
-            this_string.replace_range(0.. if n >= this_string.len() {this_string.len()} else {n}, "");
-            other_string.replace_range(0.. if n >= other_string.len() {other_string.len()} else {n}, "");
-        } 
+        left -= invalid;
+        offset += invalid;
+    }
+    bytes.len()
+}
 
-        if config.ignore {
-            this_string = this_string.to_lowercase();
-            other_string = other_string.to_lowercase();
+// Full Unicode case folding, so "Straße" matches "STRASSE". Invalid bytes
+// are left as they are.
+fn fold_case(bytes: &[u8]) -> Vec<u8> {
+    let mut folded = Vec::with_capacity(bytes.len());
+    for chunk in bytes.utf8_chunks() {
+        folded.extend_from_slice(caseless::default_case_fold_str(chunk.valid()).as_bytes());
+        folded.extend_from_slice(chunk.invalid());
+    }
+    folded
+}
+
+// --mask replaces the parts of a line that vary between otherwise equal log
+// lines with a placeholder: UUIDs, IPv4 and IPv6 addresses, hex numbers
+// with 0x or at least six hex digits, and any other run of digits, with a
+// decimal part if it has one. All but numbers must stand as a whole word.
+fn mask(bytes: &[u8]) -> Vec<u8> {
+    let mut masked = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let whole_word = |&(n, _): &(usize, &[u8])| !bytes.get(i + n).copied().is_some_and(is_word);
+        let found = if i == 0 || !is_word(bytes[i - 1]) {
+            [match_uuid, match_ipv4, match_ipv6, match_hex]
+                .iter()
+                .find_map(|m| m(&bytes[i..]).filter(whole_word))
+        } else {
+            None
+        };
+        match found.or_else(|| match_number(&bytes[i..]).map(|n| (n, &b"<num>"[..]))) {
+            Some((n, placeholder)) => {
+                masked.extend_from_slice(placeholder);
+                i += n;
+            }
+            None => {
+                masked.push(bytes[i]);
+                i += 1;
+            }
         }
-        this_string == other_string
+    }
+    masked
+}
+
+fn is_word(b: u8) -> bool {
+    b.is_ascii_alphanumeric() || b == b'_'
+}
+
+fn hex_run(bytes: &[u8]) -> usize {
+    bytes.iter().take_while(|b| b.is_ascii_hexdigit()).count()
+}
+
+fn match_uuid(bytes: &[u8]) -> Option<(usize, &'static [u8])> {
+    let mut n = 0;
+    for (i, len) in [8, 4, 4, 4, 12].into_iter().enumerate() {
+        if i > 0 {
+            if bytes.get(n) != Some(&b'-') {
+                return None;
+            }
+            n += 1;
+        }
+        if hex_run(&bytes[n..]) != len {
+            return None;
+        }
+        n += len;
+    }
+    Some((n, b"<uuid>"))
+}
+
+fn match_ipv4(bytes: &[u8]) -> Option<(usize, &'static [u8])> {
+    let mut n = 0;
+    for i in 0..4 {
+        if i > 0 {
+            if bytes.get(n) != Some(&b'.') {
+                return None;
+            }
+            n += 1;
+        }
+        let digits = bytes[n..].iter().take_while(|b| b.is_ascii_digit()).count();
+        if !(1..=3).contains(&digits) {
+            return None;
+        }
+        n += digits;
+    }
+    Some((n, b"<ip>"))
+}
+
+// Groups of up to four hex digits split by colons, either eight of them or
+// fewer with one "::", which keeps times like 12:03:44 out.
+fn match_ipv6(bytes: &[u8]) -> Option<(usize, &'static [u8])> {
+    let n = bytes.iter().take_while(|b| b.is_ascii_hexdigit() || **b == b':').count();
+    let text = &bytes[..n];
+    let groups: Vec<&[u8]> = text.split(|b| *b == b':').collect();
+    let compressed = text.windows(2).filter(|w| w == b"::").count();
+    let valid = groups.iter().all(|g| g.len() <= 4)
+        && groups.iter().any(|g| !g.is_empty())
+        && match compressed {
+            0 => groups.len() == 8 && groups.iter().all(|g| !g.is_empty()),
+            1 => groups.len() <= 8,
+            _ => false,
+        };
+    if valid { Some((n, b"<ip>")) } else { None }
+}
+
+fn match_hex(bytes: &[u8]) -> Option<(usize, &'static [u8])> {
+    if let Some(rest) = bytes.strip_prefix(b"0x").or_else(|| bytes.strip_prefix(b"0X")) {
+        return match hex_run(rest) {
+            0 => None,
+            n => Some((n + 2, b"<hex>")),
+        };
+    }
+    let n = hex_run(bytes);
+    let digits = bytes[..n].iter().any(|b| b.is_ascii_digit());
+    let letters = bytes[..n].iter().any(|b| b.is_ascii_alphabetic());
+    if n >= 6 && digits && letters { Some((n, b"<hex>")) } else { None }
+}
+
+fn match_number(bytes: &[u8]) -> Option<usize> {
+    let digits = |bytes: &[u8]| bytes.iter().take_while(|b| b.is_ascii_digit()).count();
+    match digits(bytes) {
+        0 => None,
+        n if bytes.get(n) == Some(&b'.') && digits(&bytes[n + 1..]) > 0 => {
+            Some(n + 1 + digits(&bytes[n + 1..]))
+        }
+        n => Some(n),
     }
 }
 
@@ -75,8 +375,18 @@ pub fn get_flags() -> MyResult<Config> {
             Arg::with_name("f")
                 .short("f")
                 .long("skip-fields")
+                .value_name("N")
+                .help("Avoid comparing the first N fields")
                 .takes_value(true),
         )
+        .arg(
+            Arg::with_name("key_delimiter")
+                .long("key-delimiter")
+                .value_name("DELIM")
+                .help("Separate fields by DELIM instead of blanks")
+                .takes_value(true)
+                .requires("f"),
+        )
         .arg(
             Arg::with_name("i")
                 .short("i")
@@ -99,29 +409,207 @@ pub fn get_flags() -> MyResult<Config> {
                 .long("check-chars")
                 .takes_value(true),
         )
+        .arg(
+            Arg::with_name("D")
+                .short("D")
+                .help("Print all duplicate lines")
+                .conflicts_with("u"),
+        )
+        .arg(
+            Arg::with_name("all_repeated")
+                .long("all-repeated")
+                .value_name("METHOD")
+                .help("Like -D, with groups delimited by none, prepend or separate")
+                .takes_value(true)
+                .min_values(0)
+                .require_equals(true)
+                .possible_values(&["none", "prepend", "separate"])
+                .conflicts_with("u"),
+        )
+        .arg(
+            Arg::with_name("group")
+                .long("group")
+                .value_name("METHOD")
+                .help("Print all lines, groups delimited by separate, prepend, append or both")
+                .takes_value(true)
+                .min_values(0)
+                .require_equals(true)
+                .possible_values(&["separate", "prepend", "append", "both"])
+                .conflicts_with_all(&["d", "u", "D", "all_repeated"]),
+        )
+        .arg(
+            Arg::with_name("global")
+                .long("global")
+                .help("Drop duplicates anywhere in the input, keeping the first of each")
+                .conflicts_with_all(&["D", "all_repeated", "group"]),
+        )
+        .arg(
+            Arg::with_name("memory_limit")
+                .long("memory-limit")
+                .value_name("SIZE")
+                .help("Memory --global may use before spilling to temporary files [default: 1G]")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("count_sorted")
+                .long("count-sorted")
+                .help("Count every distinct line anywhere in the input, most frequent first")
+                .conflicts_with_all(&["d", "u", "D", "all_repeated", "group", "global"]),
+        )
+        .arg(
+            Arg::with_name("top")
+                .long("top")
+                .value_name("N")
+                .help("Print only the N most frequent lines")
+                .takes_value(true)
+                .requires("count_sorted"),
+        )
+        .arg(
+            Arg::with_name("min_count")
+                .long("min-count")
+                .value_name("N")
+                .help("Print only lines seen at least N times")
+                .takes_value(true)
+                .requires("count_sorted"),
+        )
+        .arg(
+            Arg::with_name("percent")
+                .long("percent")
+                .help("Print each count as a percentage of all lines too")
+                .requires("count_sorted"),
+        )
+        .arg(
+            Arg::with_name("tie_break")
+                .long("tie-break")
+                .value_name("ORDER")
+                .help("Order lines with equal counts by first appearance or lexically")
+                .takes_value(true)
+                .possible_values(&["first", "lexical"])
+                .requires("count_sorted"),
+        )
+        .arg(
+            Arg::with_name("mask")
+                .long("mask")
+                .help("Compare lines with numbers, UUIDs, hex and IP addresses masked out"),
+        )
+        .arg(
+            Arg::with_name("similarity")
+                .long("similarity")
+                .value_name("THRESHOLD")
+                .help("Group adjacent lines whose edit distance over length is under THRESHOLD")
+                .takes_value(true)
+                .conflicts_with_all(&["global", "count_sorted"]),
+        )
+        .arg(
+            Arg::with_name("graphemes")
+                .long("graphemes")
+                .help("Count -s and -w in grapheme clusters rather than characters"),
+        )
         .get_matches();
     let in_file = matches.value_of("in_file").unwrap().to_string();
     let out_file = matches.value_of("out_file").map(String::from);
     let count = matches.is_present("c");
     let r = matches.is_present("d");
     let ignore= matches.is_present("i");
-    let skip_chars = matches.value_of("s").map(parse_positive_int).transpose()?;
+    let skip_fields = matches
+        .value_of("f")
+        .map(|x| parse_count(x, "fields to skip"))
+        .transpose()?;
+    let key_delimiter = matches.value_of("key_delimiter").map(parse_delimiter).transpose()?;
+    let skip_chars = matches
+        .value_of("s")
+        .map(|x| parse_count(x, "characters to skip"))
+        .transpose()?;
     let u= matches.is_present("u");
-    let check_chars = matches.value_of("w").map(parse_positive_int).transpose()?;
+    let check_chars = matches
+        .value_of("w")
+        .map(|x| parse_count(x, "characters to compare"))
+        .transpose()?;
 
     let repeated = if r && u || !r && !u {true} else {r};
     let unique = if r && u || !r && !u {true} else {u};
+    let all_repeated = if matches.is_present("D") || matches.is_present("all_repeated") {
+        Some(match matches.value_of("all_repeated") {
+            Some("prepend") => Delimit::Prepend,
+            Some("separate") => Delimit::Separate,
+            _ => Delimit::None,
+        })
+    } else {
+        None
+    };
+    let group = if matches.is_present("group") {
+        Some(match matches.value_of("group") {
+            Some("prepend") => Group::Prepend,
+            Some("append") => Group::Append,
+            Some("both") => Group::Both,
+            _ => Group::Separate,
+        })
+    } else {
+        None
+    };
 
+    let count_sorted = if matches.is_present("count_sorted") {
+        Some(CountSorted {
+            top: matches
+                .value_of("top")
+                .map(|x| parse_count(x, "lines to print"))
+                .transpose()?,
+            min_count: matches
+                .value_of("min_count")
+                .map(|x| parse_count(x, "occurrences"))
+                .transpose()?
+                .unwrap_or(0) as u64,
+            percent: matches.is_present("percent"),
+            lexical: matches.value_of("tie_break") == Some("lexical"),
+        })
+    } else {
+        None
+    };
 
     Ok(Config {
-        in_file, out_file, count, repeated, ignore, skip_chars, unique, check_chars
+        in_file, out_file, count, repeated, ignore, skip_fields, key_delimiter, skip_chars, unique,
+        check_chars, graphemes: matches.is_present("graphemes"), all_repeated, group,
+        global: matches.is_present("global"),
+        memory_limit: matches
+            .value_of("memory_limit")
+            .map(parse_memory_limit)
+            .transpose()?
+            .unwrap_or(1 << 30),
+        count_sorted,
+        mask: matches.is_present("mask"),
+        comparator: match matches.value_of("similarity") {
+            Some(x) => Box::new(Similarity::new(parse_threshold(x)?)),
+            None => Box::new(Exact),
+        },
     })
 }
 
-fn parse_positive_int(x: &str) -> MyResult<usize> {
+fn parse_count(x: &str, what: &str) -> MyResult<usize> {
+    x.parse()
+        .map_err(|_| format!("{}: invalid number of {}", x, what).into())
+}
+
+fn parse_threshold(x: &str) -> MyResult<f64> {
     match x.parse() {
-        Ok(n) if n > 0 => Ok(n),
-        _ => Err(From::from("parse positive int failed")),    
+        Ok(n) if (0.0..=1.0).contains(&n) => Ok(n),
+        _ => Err(format!("invalid similarity threshold -- {}", x).into()),
+    }
+}
+
+fn parse_memory_limit(x: &str) -> MyResult<usize> {
+    match headr::parse_size(x) {
+        Ok(n) if n >= MIN_MEMORY_LIMIT as u64 => Ok(n as usize),
+        Ok(_) => Err(format!("invalid memory limit -- {}: must be at least 64K", x).into()),
+        Err(e) => Err(format!("invalid memory limit -- {}: {}", x, e).into()),
+    }
+}
+
+fn parse_delimiter(x: &str) -> MyResult<char> {
+    let mut chars = x.chars();
+    match (x, chars.next(), chars.next()) {
+        ("\\t", _, _) => Ok('\t'),
+        (_, Some(c), None) => Ok(c),
+        _ => Err(format!("the delimiter must be a single character: '{}'", x).into()),
     }
 }
 
@@ -132,44 +620,430 @@ fn open(filename: &str) -> MyResult<Box<dyn BufRead>> {
     }
 }
 
-fn print(file: &mut Box<dyn Write>, count: usize, line: &str, config: &Config) {
-    match (config.repeated, config.unique, config.count, count > 1) {
-        (true, true, true, _) |
-        (true, false, true, true) |
-        (false, true, true, false) => write!(file, "{} {}\n", count, line),
+// A run of adjacent equal lines. Only the first line is kept, unless -D
+// or --group will print them all.
+struct LineGroup {
+    lines: Vec<Vec<u8>>,
+    count: usize,
+}
+
+struct Printer {
+    out: Box<dyn Write>,
+    printed: usize,
+}
 
-        (true, true, false, _) | 
-        (true, false, false, true) | 
-        (false, true, false, false) => write!(file, "{}\n", line),
-        _ => Ok(()),
-    }.unwrap();
+impl Printer {
+    fn print(&mut self, group: &LineGroup, config: &Config) -> io::Result<()> {
+        let lines = match (config.group, config.all_repeated) {
+            (Some(mode), _) => {
+                if self.printed > 0 || matches!(mode, Group::Prepend | Group::Both) {
+                    self.out.write_all(b"\n")?;
+                }
+                &group.lines[..]
+            }
+            (None, Some(delimit)) => {
+                if group.count < 2 {
+                    return Ok(());
+                }
+                if delimit == Delimit::Prepend || (delimit == Delimit::Separate && self.printed > 0) {
+                    self.out.write_all(b"\n")?;
+                }
+                &group.lines[..]
+            }
+            (None, None) => {
+                let show = if group.count > 1 { config.repeated } else { config.unique };
+                if !show {
+                    return Ok(());
+                }
+                &group.lines[..1]
+            }
+        };
+        for line in lines {
+            if config.count {
+                write!(self.out, "{} ", group.count)?;
+            }
+            self.out.write_all(line)?;
+            self.out.write_all(b"\n")?;
+        }
+        self.printed += 1;
+        Ok(())
+    }
+
+    fn finish(&mut self, config: &Config) -> io::Result<()> {
+        if self.printed > 0 && matches!(config.group, Some(Group::Append | Group::Both)) {
+            self.out.write_all(b"\n")?;
+        }
+        self.out.flush()
+    }
 }
 
 pub fn run(config: &Config) -> MyResult<()> {
-    let file = open(&config.in_file)?;
+    let mut file = open(&config.in_file).map_err(|e| format!("{}: {}", config.in_file, e))?;
 
-    let mut out_file: Box<dyn Write> = match &config.out_file {
-        Some(filename) => Box::new(File::create(filename)?),
-        _ => Box::new(io::stdout()),
+    let out: Box<dyn Write> = match &config.out_file {
+        Some(filename) => Box::new(BufWriter::new(
+            File::create(filename).map_err(|e| format!("{}: {}", filename, e))?,
+        )),
+        _ => Box::new(BufWriter::new(io::stdout())),
     };
+    let mut printer = Printer { out, printed: 0 };
+    if let Some(sorted) = &config.count_sorted {
+        run_count_sorted(config, sorted, &mut file, &mut printer)?;
+        printer.finish(config)?;
+        return Ok(());
+    }
+    if config.global {
+        tally(config, &mut file, |entry| print_entry(&mut printer, entry, config))?;
+        printer.finish(config)?;
+        return Ok(());
+    }
+    let keep_all = config.all_repeated.is_some() || config.group.is_some();
+
+    // Lines are bytes, so invalid UTF-8 is written back out untouched.
+    let mut line = vec![];
+    let mut group: Option<LineGroup> = None;
+    loop {
+        line.clear();
+        if file.read_until(b'\n', &mut line)? == 0 {
+            break;
+        }
+        if line.last() == Some(&b'\n') {
+            line.pop();
+        }
+        match &mut group {
+            Some(group) if ConditionalEq::eq(&line, &group.lines[0], config) => {
+                group.count += 1;
+                if keep_all {
+                    group.lines.push(line.clone());
+                }
+            }
+            _ => {
+                // reach a different line
+                if let Some(group) = &group {
+                    printer.print(group, config)?;
+                }
+                group = Some(LineGroup { lines: vec![line.clone()], count: 1 });
+            }
+        }
+    }
+    if let Some(group) = &group {
+        printer.print(group, config)?;
+    }
+    printer.finish(config)?;
+
+    Ok(())
+}
+
+// --global and --count-sorted keep the first line of every distinct key in
+// a hash map, with where it appeared and how often its key occurs. When the
+// map grows past --memory-limit it is spilled into temporary partition
+// files by hash of key, so every copy of a key lands in the same one. Each
+// partition is then merged the same way, and spilled again a level down if
+// its keys still don't fit, until every partition fits and is written out
+// as a run sorted by first appearance. The runs are merged back into input
+// order, so entries come out of tally in the order their keys first
+// appeared.
+const PARTITIONS: usize = 64;
+
+// Levels of partitions before one is merged in memory whatever its size.
+// Every level hashes with a different salt, so only keys that collide at
+// each level can stay together that long.
+const MAX_DEPTH: u32 = 4;
+
+// Runs are merged this many at a time, which bounds the files held open.
+const FAN_IN: usize = 64;
+
+// Smallest --memory-limit accepted. Below this a map holds too few entries
+// for spilling to get anywhere, and every level just makes more files.
+const MIN_MEMORY_LIMIT: usize = 64 << 10;
+
+// Rough cost of a map entry beyond its key and line.
+const ENTRY_OVERHEAD: usize = 96;
+
+struct Entry {
+    index: u64,
+    count: u64,
+    line: Vec<u8>,
+}
+
+impl Entry {
+    fn merge(&mut self, other: Entry) {
+        self.count += other.count;
+        if other.index < self.index {
+            self.index = other.index;
+            self.line = other.line;
+        }
+    }
+}
+
+fn cost(key: &[u8], entry: &Entry) -> usize {
+    key.len() + entry.line.len() + ENTRY_OVERHEAD
+}
+
+// A partition file, and what its records would cost back in a map.
+struct Partition {
+    file: BufWriter<File>,
+    cost: usize,
+}
+
+// Sorted runs by tier. FAN_IN runs of one tier are merged into a single run
+// of the next.
+type Runs = Vec<Vec<BufReader<File>>>;
+
+type Next<'a> = dyn FnMut() -> io::Result<Option<(Vec<u8>, Entry)>> + 'a;
+
+fn tally(
+    config: &Config,
+    file: &mut Box<dyn BufRead>,
+    emit: impl FnMut(Entry) -> io::Result<()>,
+) -> MyResult<()> {
+    let mut index = 0;
+    let mut next = || {
+        let mut line = vec![];
+        if file.read_until(b'\n', &mut line)? == 0 {
+            return Ok(None);
+        }
+        if line.last() == Some(&b'\n') {
+            line.pop();
+        }
+        let key = key(&line, config).into_owned();
+        index += 1;
+        Ok(Some((key, Entry { index: index - 1, count: 1, line })))
+    };
+    let mut runs = Runs::new();
+    match merge_entries(&mut next, config.memory_limit, 0, PARTITIONS, &mut runs)? {
+        Some(entries) => entries.into_iter().try_for_each(emit)?,
+        None => merge_runs(runs.into_iter().flatten().collect(), emit)?,
+    }
+    Ok(())
+}
+
+// Folds entries with the same key together. If they all fit within limit
+// they are returned sorted by first appearance. Otherwise they are spilled
+// into partitions, each partition is merged in turn, and what comes of it
+// goes into runs.
+fn merge_entries(
+    next: &mut Next,
+    limit: usize,
+    depth: u32,
+    fanout: usize,
+    runs: &mut Runs,
+) -> io::Result<Option<Vec<Entry>>> {
+    let mut seen: HashMap<Vec<u8>, Entry> = HashMap::new();
+    let mut used = 0;
+    let mut partitions = vec![];
+    while let Some((key, entry)) = next()? {
+        match seen.entry(key) {
+            hash_map::Entry::Occupied(mut old) => old.get_mut().merge(entry),
+            hash_map::Entry::Vacant(new) => {
+                used += cost(new.key(), &entry);
+                new.insert(entry);
+            }
+        }
+        // A single key can't be split any further, so it stays in memory.
+        if used > limit && seen.len() > 1 && depth < MAX_DEPTH {
+            spill(&mut seen, &mut partitions, depth, fanout)?;
+            used = 0;
+        }
+    }
+
+    if partitions.is_empty() {
+        let mut entries: Vec<_> = seen.into_values().collect();
+        entries.sort_unstable_by_key(|entry| entry.index);
+        return Ok(Some(entries));
+    }
+    spill(&mut seen, &mut partitions, depth, fanout)?;
+
+    for partition in partitions {
+        // Enough partitions a level down for each to fit, should this one
+        // have to spill again.
+        let fanout = (partition.cost / limit + 1).clamp(2, PARTITIONS);
+        let mut reader = rewind(partition.file)?;
+        let mut next = || read_record(&mut reader);
+        if let Some(entries) = merge_entries(&mut next, limit, depth + 1, fanout, runs)? {
+            let mut run = BufWriter::new(tempfile::tempfile()?);
+            for entry in &entries {
+                write_record(&mut run, &[], entry)?;
+            }
+            add_run(runs, 0, rewind(run)?)?;
+        }
+    }
+    Ok(None)
+}
+
+fn add_run(runs: &mut Runs, tier: usize, run: BufReader<File>) -> io::Result<()> {
+    if runs.len() == tier {
+        runs.push(vec![]);
+    }
+    runs[tier].push(run);
+    if runs[tier].len() == FAN_IN {
+        let mut merged = BufWriter::new(tempfile::tempfile()?);
+        merge_runs(std::mem::take(&mut runs[tier]), |entry| write_record(&mut merged, &[], &entry))?;
+        add_run(runs, tier + 1, rewind(merged)?)?;
+    }
+    Ok(())
+}
+
+// Merges sorted runs, smallest first appearance first.
+fn merge_runs(
+    mut runs: Vec<BufReader<File>>,
+    mut emit: impl FnMut(Entry) -> io::Result<()>,
+) -> io::Result<()> {
+    let mut heads = BinaryHeap::new();
+    let mut pending = vec![];
+    for (i, run) in runs.iter_mut().enumerate() {
+        let head = read_record(run)?.map(|(_, entry)| entry);
+        if let Some(entry) = &head {
+            heads.push(Reverse((entry.index, i)));
+        }
+        pending.push(head);
+    }
+    while let Some(Reverse((_, i))) = heads.pop() {
+        if let Some(entry) = pending[i].take() {
+            emit(entry)?;
+        }
+        pending[i] = read_record(&mut runs[i])?.map(|(_, entry)| entry);
+        if let Some(entry) = &pending[i] {
+            heads.push(Reverse((entry.index, i)));
+        }
+    }
+    Ok(())
+}
 
-    let mut lines = file.lines();
-    let first_line = lines.next().unwrap().unwrap();
-    let mut last_line = first_line;
-    let mut count = 1;
-    for line in lines.map(|l| l.unwrap()) {
-        if ConditionalEq::eq(&line, &last_line, &config){
-            count += 1;
-        } else { // reach a different line
-            // println!("{}  {}", count, last_line);
-            print(&mut out_file, count, &last_line, &config);
+fn run_count_sorted(
+    config: &Config,
+    sorted: &CountSorted,
+    file: &mut Box<dyn BufRead>,
+    printer: &mut Printer,
+) -> MyResult<()> {
+    let mut entries = vec![];
+    let mut total = 0;
+    tally(config, file, |entry| {
+        total += entry.count;
+        if entry.count >= sorted.min_count {
+            entries.push(entry);
+        }
+        Ok(())
+    })?;
 
-            count = 1;
-            last_line = line;
+    // Entries arrive in order of first appearance, which a stable sort
+    // keeps for ties.
+    if sorted.lexical {
+        entries.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.line.cmp(&b.line)));
+    } else {
+        entries.sort_by_key(|entry| Reverse(entry.count));
+    }
+    entries.truncate(sorted.top.unwrap_or(usize::MAX));
+    for entry in entries {
+        write!(printer.out, "{} ", entry.count)?;
+        if sorted.percent {
+            write!(printer.out, "{:>6.2}% ", 100.0 * entry.count as f64 / total as f64)?;
         }
+        printer.out.write_all(&entry.line)?;
+        printer.out.write_all(b"\n")?;
     }
-    // println!("{}  {}", count, last_line);
-    print(&mut out_file, count, &last_line, &config);
-    
     Ok(())
 }
+
+fn print_entry(printer: &mut Printer, entry: Entry, config: &Config) -> io::Result<()> {
+    let group = LineGroup { lines: vec![entry.line], count: entry.count as usize };
+    printer.print(&group, config)
+}
+
+fn spill(
+    seen: &mut HashMap<Vec<u8>, Entry>,
+    partitions: &mut Vec<Partition>,
+    depth: u32,
+    fanout: usize,
+) -> io::Result<()> {
+    if partitions.is_empty() {
+        for _ in 0..fanout {
+            partitions.push(Partition { file: BufWriter::new(tempfile::tempfile()?), cost: 0 });
+        }
+    }
+    for (key, entry) in seen.drain() {
+        let mut hasher = DefaultHasher::new();
+        (depth, &key).hash(&mut hasher);
+        let n = partitions.len() as u64;
+        let partition = &mut partitions[(hasher.finish() % n) as usize];
+        partition.cost += cost(&key, &entry);
+        write_record(&mut partition.file, &key, &entry)?;
+    }
+    Ok(())
+}
+
+fn rewind(file: BufWriter<File>) -> io::Result<BufReader<File>> {
+    let mut file = file.into_inner().map_err(|e| e.into_error())?;
+    file.seek(SeekFrom::Start(0))?;
+    Ok(BufReader::new(file))
+}
+
+// Records are the index and count, then the key and the line, each after
+// its length.
+fn write_record(out: &mut impl Write, key: &[u8], entry: &Entry) -> io::Result<()> {
+    out.write_all(&entry.index.to_le_bytes())?;
+    out.write_all(&entry.count.to_le_bytes())?;
+    for field in [key, &entry.line] {
+        out.write_all(&(field.len() as u64).to_le_bytes())?;
+        out.write_all(field)?;
+    }
+    Ok(())
+}
+
+fn read_record(input: &mut impl Read) -> io::Result<Option<(Vec<u8>, Entry)>> {
+    let mut index = [0; 8];
+    match input.read_exact(&mut index) {
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+        result => result?,
+    }
+    let count = read_u64(input)?;
+    let mut fields = [vec![], vec![]];
+    for field in &mut fields {
+        let len = read_u64(input)?;
+        input.take(len).read_to_end(field)?;
+    }
+    let [key, line] = fields;
+    Ok(Some((key, Entry { index: u64::from_le_bytes(index), count, line })))
+}
+
+fn read_u64(input: &mut impl Read) -> io::Result<u64> {
+    let mut buf = [0; 8];
+    input.read_exact(&mut buf)?;
+    Ok(u64::from_le_bytes(buf))
+}
+
+#[cfg(test)]
+mod test {
+    use super::{advance, edit_distance, mask};
+
+    #[test]
+    fn test_advance() {
+        assert_eq!(advance(b"abc", 2, false), 2);
+        assert_eq!(advance(b"abc", 5, false), 3);
+        assert_eq!(advance("aβc".as_bytes(), 2, false), 3);
+        assert_eq!(advance(b"\xff\xfeab", 3, false), 3);
+        assert_eq!(advance("e\u{301}x".as_bytes(), 1, false), 1);
+        assert_eq!(advance("e\u{301}x".as_bytes(), 1, true), 3);
+        assert_eq!(advance(b"", 1, true), 0);
+    }
+
+    #[test]
+    fn test_edit_distance() {
+        let chars = |s: &str| s.chars().collect::<Vec<_>>();
+        assert_eq!(edit_distance(&chars(""), &chars("abc")), 3);
+        assert_eq!(edit_distance(&chars("kitten"), &chars("sitting")), 3);
+        assert_eq!(edit_distance(&chars("αβγ"), &chars("αγ")), 1);
+    }
+
+    #[test]
+    fn test_mask() {
+        let masked = |s: &str| String::from_utf8(mask(s.as_bytes())).unwrap();
+        assert_eq!(masked("took 12ms, 3.5s"), "took <num>ms, <num>s");
+        assert_eq!(masked("user42 at 12:03:44"), "user<num> at <num>:<num>:<num>");
+        assert_eq!(masked("from 10.0.0.1:8080"), "from <ip>:<num>");
+        assert_eq!(masked("from fe80::1 and ::1"), "from <ip> and <ip>");
+        assert_eq!(masked("id 123e4567-e89b-12d3-a456-426614174000."), "id <uuid>.");
+        assert_eq!(masked("at 0x7ffd and deadbeef1"), "at <hex> and <hex>");
+        assert_eq!(masked("cafe face"), "cafe face");
+    }
+}
@@ -1,4 +1,6 @@
 fn main() {
-    let config = uniqr::get_flags().unwrap();
-    uniqr::run(&config).unwrap();
-}
\ No newline at end of file
+    if let Err(e) = uniqr::get_flags().and_then(|config| uniqr::run(&config)) {
+        eprintln!("uniqr: {}", e);
+        std::process::exit(1);
+    }
+}
@@ -0,0 +1,731 @@
+use assert_cmd::Command;
+use predicates::prelude::*;
+use std::fs;
+use tempfile::NamedTempFile;
+
+type TestResult = Result<(), Box<dyn std::error::Error>>;
+
+const PRG: &str = "uniqr";
+const FIELDS: &str = "tests/inputs/fields.txt";
+const LOG: &str = "tests/inputs/log.tsv";
+const EMPTY: &str = "tests/inputs/empty.txt";
+const INVALID: &str = "tests/inputs/invalid.bin";
+const CASE: &str = "tests/inputs/case.txt";
+const ACCESS: &str = "tests/inputs/access.log";
+const APP: &str = "tests/inputs/app.log";
+
+// An input and the prefix of its expected outputs, which mk-outs.sh makes
+// with GNU uniq.
+struct Test {
+    input: &'static str,
+    expected: &'static str,
+}
+
+const EMPTY_INPUT: Test = Test { input: EMPTY, expected: "tests/expected/empty.txt" };
+const ONE: Test = Test { input: "tests/inputs/one.txt", expected: "tests/expected/one.txt" };
+const TWO: Test = Test { input: "tests/inputs/two.txt", expected: "tests/expected/two.txt" };
+const THREE: Test = Test { input: "tests/inputs/three.txt", expected: "tests/expected/three.txt" };
+const SKIP: Test = Test { input: "tests/inputs/skip.txt", expected: "tests/expected/skip.txt" };
+const T1: Test = Test { input: "tests/inputs/t1.txt", expected: "tests/expected/t1.txt" };
+const T2: Test = Test { input: "tests/inputs/t2.txt", expected: "tests/expected/t2.txt" };
+const T3: Test = Test { input: "tests/inputs/t3.txt", expected: "tests/expected/t3.txt" };
+const T4: Test = Test { input: "tests/inputs/t4.txt", expected: "tests/expected/t4.txt" };
+const T5: Test = Test { input: "tests/inputs/t5.txt", expected: "tests/expected/t5.txt" };
+const T6: Test = Test { input: "tests/inputs/t6.txt", expected: "tests/expected/t6.txt" };
+
+// --------------------------------------------------
+fn run(args: &[&str], expected_file: &str) -> TestResult {
+    let expected = fs::read_to_string(expected_file)?;
+    Command::cargo_bin(PRG)?
+        .args(args)
+        .assert()
+        .success()
+        .stdout(expected);
+    Ok(())
+}
+
+// --------------------------------------------------
+fn run_stdin(args: &[&str], input: &str, expected: &str) -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(args)
+        .write_stdin(input)
+        .assert()
+        .success()
+        .stdout(expected.to_string());
+    Ok(())
+}
+
+// --------------------------------------------------
+// Plain and counted output, from a file or stdin, to stdout or a file
+fn run_basic(test: &Test) -> TestResult {
+    for (args, suffix) in [(vec![], "out"), (vec!["-c"], "c.out")] {
+        let expected_file = format!("{}.{}", test.expected, suffix);
+        let expected = fs::read_to_string(&expected_file)?;
+        run(&[&[test.input][..], &args].concat(), &expected_file)?;
+
+        Command::cargo_bin(PRG)?
+            .args(&args)
+            .write_stdin(fs::read(test.input)?)
+            .assert()
+            .success()
+            .stdout(expected.clone());
+
+        let outfile = NamedTempFile::new()?;
+        let outpath = outfile.path().to_str().unwrap();
+        Command::cargo_bin(PRG)?
+            .args([&[test.input, outpath][..], &args].concat())
+            .assert()
+            .success()
+            .stdout("");
+        assert_eq!(expected, fs::read_to_string(outpath)?);
+    }
+    Ok(())
+}
+
+// --------------------------------------------------
+fn run_all_repeated(test: &Test) -> TestResult {
+    for (arg, suffix) in [
+        ("-D", "D.out"),
+        ("--all-repeated", "D.out"),
+        ("--all-repeated=none", "D.out"),
+        ("--all-repeated=prepend", "D-prepend.out"),
+        ("--all-repeated=separate", "D-separate.out"),
+    ] {
+        run(&[arg, test.input], &format!("{}.{}", test.expected, suffix))?;
+    }
+    Ok(())
+}
+
+// --------------------------------------------------
+fn run_group(test: &Test) -> TestResult {
+    for (arg, suffix) in [
+        ("--group", "group.out"),
+        ("--group=separate", "group.out"),
+        ("--group=prepend", "group-prepend.out"),
+        ("--group=append", "group-append.out"),
+        ("--group=both", "group-both.out"),
+    ] {
+        run(&[arg, test.input], &format!("{}.{}", test.expected, suffix))?;
+    }
+    Ok(())
+}
+
+// --------------------------------------------------
+fn run_global(input: &str) -> TestResult {
+    let basename = input.rsplit('/').next().unwrap();
+    for (flags, suffix) in [
+        (&[][..], "global.out"),
+        (&["-c"][..], "global.c.out"),
+        (&["-d"][..], "global.d.out"),
+        (&["-u"][..], "global.u.out"),
+    ] {
+        let args = [&["--global"][..], flags, &[input]].concat();
+        run(&args, &format!("tests/expected/{}.{}", basename, suffix))?;
+    }
+    Ok(())
+}
+
+// --------------------------------------------------
+// Lines that repeat far apart, with every tenth line seen only once, so
+// each of -c, -d and -u has something to show.
+fn scattered(lines: usize, distinct: usize) -> String {
+    (0..lines)
+        .map(|i| match i % 10 {
+            0 => format!("once {}\n", i),
+            _ => format!("line {}\n", (i * 7919) % distinct),
+        })
+        .collect()
+}
+
+// --------------------------------------------------
+// A --memory-limit small enough to spill must not change the output.
+fn run_spilled(args: &[&str], input: &str, limit: &str) -> TestResult {
+    let expected = Command::cargo_bin(PRG)?.args(args).write_stdin(input).output()?.stdout;
+    assert!(!expected.is_empty());
+    Command::cargo_bin(PRG)?
+        .args([args, &["--memory-limit", limit]].concat())
+        .write_stdin(input)
+        .assert()
+        .success()
+        .stdout(expected);
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_bad_skip_fields() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["-f", "x", FIELDS])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("x: invalid number of fields to skip"));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_bad_key_delimiter() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["-f", "1", "--key-delimiter", "::", FIELDS])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("single character"));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_key_delimiter_without_skip_fields() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["--key-delimiter", ",", FIELDS])
+        .assert()
+        .failure();
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn skip_fields() -> TestResult {
+    run(&["-f", "1", FIELDS], "tests/expected/fields.txt.f1.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn skip_fields_2() -> TestResult {
+    run(&["--skip-fields", "2", FIELDS], "tests/expected/fields.txt.f2.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn skip_fields_ignore_case() -> TestResult {
+    run(&["-f", "1", "-i", FIELDS], "tests/expected/fields.txt.f1.i.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn skip_fields_then_chars() -> TestResult {
+    run(&["-f", "1", "-s", "2", FIELDS], "tests/expected/fields.txt.f1.s2.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn skip_fields_then_check_chars() -> TestResult {
+    run(&["-f", "1", "-w", "2", FIELDS], "tests/expected/fields.txt.f1.w2.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn skip_all_fields() -> TestResult {
+    run(&["-f", "9", FIELDS], "tests/expected/fields.txt.f9.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn key_delimiter() -> TestResult {
+    run(&["-f", "1", "--key-delimiter", "\t", LOG], "tests/expected/log.tsv.d1.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn key_delimiter_escaped_tab() -> TestResult {
+    run(&["-f", "2", "--key-delimiter", "\\t", LOG], "tests/expected/log.tsv.d2.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn empty() -> TestResult {
+    run(&[EMPTY], EMPTY)?;
+    run_stdin(&["-c"], "", "")
+}
+
+// --------------------------------------------------
+#[test]
+fn skip_chars_multibyte() -> TestResult {
+    run_stdin(&["-s", "2"], "a\u{3b2}c\nb\u{3b3}c\nc\u{3b4}d\n", "a\u{3b2}c\nc\u{3b4}d\n")
+}
+
+// --------------------------------------------------
+#[test]
+fn check_chars_multibyte() -> TestResult {
+    run_stdin(&["-w", "2"], "\u{f1}x1\n\u{f1}x2\n\u{f1}o3\n", "\u{f1}x1\n\u{f1}o3\n")
+}
+
+// --------------------------------------------------
+#[test]
+fn ignore_case_folds() -> TestResult {
+    run_stdin(
+        &["-i", "-c"],
+        "Stra\u{df}e\nSTRASSE\nstra\u{df}e\n\u{3a3}\u{391}\u{3a3}\n\u{3c3}\u{3b1}\u{3c2}\n",
+        "3 Stra\u{df}e\n2 \u{3a3}\u{391}\u{3a3}\n",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn check_graphemes() -> TestResult {
+    let input = "e\u{301}a\nea\n";
+    run_stdin(&["-w", "1"], input, "e\u{301}a\n")?;
+    run_stdin(&["-w", "1", "--graphemes"], input, input)
+}
+
+// --------------------------------------------------
+#[test]
+fn invalid_utf8() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .arg(INVALID)
+        .assert()
+        .success()
+        .stdout(&b"\xffa\n\xfea\nb\xff\n"[..]);
+    Command::cargo_bin(PRG)?
+        .args(["-c", "-s", "1", INVALID])
+        .assert()
+        .success()
+        .stdout(&b"3 \xffa\n1 b\xff\n"[..]);
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_bad_file() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .arg("tests/inputs/no-such-file")
+        .assert()
+        .failure()
+        .stderr(predicate::str::is_match("uniqr: tests/inputs/no-such-file: .* [(]os error 2[)]")?);
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn empty_input() -> TestResult {
+    run_basic(&EMPTY_INPUT)
+}
+
+#[test]
+fn empty_all_repeated() -> TestResult {
+    run_all_repeated(&EMPTY_INPUT)
+}
+
+#[test]
+fn empty_group() -> TestResult {
+    run_group(&EMPTY_INPUT)
+}
+
+// --------------------------------------------------
+#[test]
+fn one() -> TestResult {
+    run_basic(&ONE)
+}
+
+#[test]
+fn one_all_repeated() -> TestResult {
+    run_all_repeated(&ONE)
+}
+
+#[test]
+fn one_group() -> TestResult {
+    run_group(&ONE)
+}
+
+// --------------------------------------------------
+#[test]
+fn two() -> TestResult {
+    run_basic(&TWO)
+}
+
+#[test]
+fn two_all_repeated() -> TestResult {
+    run_all_repeated(&TWO)
+}
+
+#[test]
+fn two_group() -> TestResult {
+    run_group(&TWO)
+}
+
+// --------------------------------------------------
+#[test]
+fn three() -> TestResult {
+    run_basic(&THREE)
+}
+
+#[test]
+fn three_all_repeated() -> TestResult {
+    run_all_repeated(&THREE)
+}
+
+#[test]
+fn three_group() -> TestResult {
+    run_group(&THREE)
+}
+
+// --------------------------------------------------
+#[test]
+fn skip() -> TestResult {
+    run_basic(&SKIP)
+}
+
+#[test]
+fn skip_all_repeated() -> TestResult {
+    run_all_repeated(&SKIP)
+}
+
+#[test]
+fn skip_group() -> TestResult {
+    run_group(&SKIP)
+}
+
+// --------------------------------------------------
+#[test]
+fn t1() -> TestResult {
+    run_basic(&T1)
+}
+
+#[test]
+fn t1_all_repeated() -> TestResult {
+    run_all_repeated(&T1)
+}
+
+#[test]
+fn t1_group() -> TestResult {
+    run_group(&T1)
+}
+
+// --------------------------------------------------
+#[test]
+fn t2() -> TestResult {
+    run_basic(&T2)
+}
+
+#[test]
+fn t2_all_repeated() -> TestResult {
+    run_all_repeated(&T2)
+}
+
+#[test]
+fn t2_group() -> TestResult {
+    run_group(&T2)
+}
+
+// --------------------------------------------------
+#[test]
+fn t3() -> TestResult {
+    run_basic(&T3)
+}
+
+#[test]
+fn t3_all_repeated() -> TestResult {
+    run_all_repeated(&T3)
+}
+
+#[test]
+fn t3_group() -> TestResult {
+    run_group(&T3)
+}
+
+// --------------------------------------------------
+#[test]
+fn t4() -> TestResult {
+    run_basic(&T4)
+}
+
+#[test]
+fn t4_all_repeated() -> TestResult {
+    run_all_repeated(&T4)
+}
+
+#[test]
+fn t4_group() -> TestResult {
+    run_group(&T4)
+}
+
+// --------------------------------------------------
+#[test]
+fn t5() -> TestResult {
+    run_basic(&T5)
+}
+
+#[test]
+fn t5_all_repeated() -> TestResult {
+    run_all_repeated(&T5)
+}
+
+#[test]
+fn t5_group() -> TestResult {
+    run_group(&T5)
+}
+
+// --------------------------------------------------
+#[test]
+fn t6() -> TestResult {
+    run_basic(&T6)
+}
+
+#[test]
+fn t6_all_repeated() -> TestResult {
+    run_all_repeated(&T6)
+}
+
+#[test]
+fn t6_group() -> TestResult {
+    run_group(&T6)
+}
+
+// --------------------------------------------------
+#[test]
+fn all_repeated_ignore_case() -> TestResult {
+    run(&["-D", "-i", CASE], "tests/expected/case.txt.D.i.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn group_ignore_case() -> TestResult {
+    run(&["--group=append", "--ignore-case", CASE], "tests/expected/case.txt.group-append.i.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn all_repeated_count() -> TestResult {
+    // Every line shows the size of its group.
+    run_stdin(
+        &["-Dci", "--all-repeated=separate"],
+        "a\nA\nb\nc\nC\nc\n",
+        "2 a\n2 A\n\n3 c\n3 C\n3 c\n",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn group_count() -> TestResult {
+    run_stdin(&["--group=prepend", "-c"], "a\na\nb\n", "\n2 a\n2 a\n\n1 b\n")
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_group_with_repeated() -> TestResult {
+    for arg in ["-d", "-u", "-D", "--all-repeated"] {
+        Command::cargo_bin(PRG)?
+            .args(["--group", arg, EMPTY])
+            .assert()
+            .failure()
+            .stderr(predicate::str::contains("cannot be used with"));
+    }
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn global() -> TestResult {
+    run_global(THREE.input)?;
+    run_global(ACCESS)
+}
+
+// --------------------------------------------------
+#[test]
+fn global_spills() -> TestResult {
+    // Small enough to spill to temporary files many times over
+    let input = scattered(20_000, 3001);
+    for flag in ["-c", "-d", "-u"] {
+        run_spilled(&["--global", flag], &input, "64K")?;
+    }
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn global_spills_partitions_again() -> TestResult {
+    // Each first-level partition is far over the limit, so it is split
+    // again, and the runs that come of that are merged in tiers.
+    run_spilled(&["--global", "-c"], &scattered(200_000, 40_009), "64K")
+}
+
+// --------------------------------------------------
+#[test]
+fn global_ignore_case() -> TestResult {
+    run_stdin(&["--global", "-i", "-c"], "b\na\nB\nc\na\n", "2 b\n2 a\n1 c\n")
+}
+
+// --------------------------------------------------
+#[test]
+fn global_empty() -> TestResult {
+    run_stdin(&["--global", "-c"], "", "")
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_bad_memory_limit() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["--global", "--memory-limit", "3X", EMPTY])
+        .assert()
+        .failure()
+        .stderr("uniqr: invalid memory limit -- 3X: invalid suffix\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_small_memory_limit() -> TestResult {
+    for limit in ["0", "1K", "65535"] {
+        Command::cargo_bin(PRG)?
+            .args(["--global", "--memory-limit", limit, EMPTY])
+            .assert()
+            .failure()
+            .stderr(format!("uniqr: invalid memory limit -- {}: must be at least 64K\n", limit));
+    }
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_global_with_group() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["--global", "--group", EMPTY])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("cannot be used with"));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn count_sorted() -> TestResult {
+    run(&["--count-sorted", ACCESS], "tests/expected/access.log.sorted.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn count_sorted_lexical() -> TestResult {
+    run(
+        &["--count-sorted", "--tie-break=lexical", ACCESS],
+        "tests/expected/access.log.sorted.lexical.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn count_sorted_top() -> TestResult {
+    run(&["--count-sorted", "--top", "3", ACCESS], "tests/expected/access.log.sorted.top3.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn count_sorted_min_count() -> TestResult {
+    run(
+        &["--count-sorted", "--min-count", "2", ACCESS],
+        "tests/expected/access.log.sorted.min2.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn count_sorted_percent() -> TestResult {
+    run(&["--count-sorted", "--percent", ACCESS], "tests/expected/access.log.sorted.percent.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn count_sorted_ignore_case() -> TestResult {
+    run(&["--count-sorted", "-i", ACCESS], "tests/expected/access.log.sorted.i.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn count_sorted_skip_chars() -> TestResult {
+    // The key starts after the method, so "GET /x" and "PUT /x" are one line.
+    run_stdin(
+        &["--count-sorted", "-s", "4"],
+        "PUT /a\nGET /b\nGET /a\n",
+        "2 PUT /a\n1 GET /b\n",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn count_sorted_spills() -> TestResult {
+    run_spilled(&["--count-sorted"], &scattered(20_000, 3001), "64K")
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_top_without_count_sorted() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["--top", "3", ACCESS])
+        .assert()
+        .failure();
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_bad_top() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["--count-sorted", "--top", "x", ACCESS])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("x: invalid number of lines to print"));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn mask() -> TestResult {
+    run(&["--mask", APP], "tests/expected/app.log.mask.out")?;
+    run(&["--mask", "-c", APP], "tests/expected/app.log.mask.c.out")?;
+    run(&["--mask", "-d", APP], "tests/expected/app.log.mask.d.out")?;
+    run(&["--mask", "-u", APP], "tests/expected/app.log.mask.u.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn mask_global() -> TestResult {
+    run_stdin(
+        &["--mask", "--global", "-c"],
+        "took 12ms\nping 10.0.0.1\ntook 7ms\nping ::1\n",
+        "2 took 12ms\n2 ping 10.0.0.1\n",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn similarity() -> TestResult {
+    run_stdin(
+        &["--similarity", "0.2", "-c"],
+        "disk /dev/sda1 at 91% full\ndisk /dev/sdb1 at 91% full\ndisk /dev/sdb2 at 97% full\nfan speed low\n",
+        "3 disk /dev/sda1 at 91% full\n1 fan speed low\n",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn similarity_against_first_line() -> TestResult {
+    // "abff" is near "abce" but not near "abcd", which leads the group.
+    run_stdin(&["--similarity", "0.3", "-c"], "abcd\nabce\nabff\n", "2 abcd\n1 abff\n")
+}
+
+// --------------------------------------------------
+#[test]
+fn similarity_zero() -> TestResult {
+    run_stdin(&["--similarity", "0", "-c"], "a\na\nb\n", "2 a\n1 b\n")
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_bad_similarity() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["--similarity", "2", APP])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("invalid similarity threshold -- 2"));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_similarity_with_global() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["--similarity", "0.2", "--global", APP])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("cannot be used with"));
+    Ok(())
+}
@@ -0,0 +1,63 @@
+#!/usr/bin/env bash
+
+# Expected outputs come from GNU wc in a UTF-8 locale
+export LC_ALL=C.UTF-8
+
+INPUTS="tests/inputs"
+OUT_DIR="./tests/expected"
+
+[[ ! -d "$OUT_DIR" ]] && mkdir -p "$OUT_DIR"
+
+for FILE in $INPUTS/*.txt; do
+    BASENAME=$(basename "$FILE")
+    wc        $FILE > ${OUT_DIR}/${BASENAME}.out
+    wc -m     $FILE > ${OUT_DIR}/${BASENAME}.m.out
+    wc -L     $FILE > ${OUT_DIR}/${BASENAME}.L.out
+    wc -lwmcL $FILE > ${OUT_DIR}/${BASENAME}.all-flags.out
+done
+
+# Binary input with NULs and invalid UTF-8
+wc -m     $INPUTS/binary.bin > $OUT_DIR/binary.bin.m.out
+wc -lwmcL $INPUTS/binary.bin > $OUT_DIR/binary.bin.all-flags.out
+
+wc       $INPUTS/fox.txt $INPUTS/empty.txt $INPUTS/atlamal.txt \
+    $INPUTS/wide.txt $INPUTS/fox.txt > $OUT_DIR/all.out
+wc -m -L $INPUTS/atlamal.txt $INPUTS/fox.txt $INPUTS/wide.txt \
+    > $OUT_DIR/all.mL.out
+wc -l    $INPUTS/atlamal.txt $INPUTS/fox.txt > $OUT_DIR/all.l.out
+
+# The total row. GNU wc 9.1 has no --total, so the rows are cut out of
+# its usual output.
+ALL="$INPUTS/fox.txt $INPUTS/atlamal.txt"
+wc $ALL | head -n -1 > $OUT_DIR/all.total-never.out
+wc $ALL | tail -n 1 | awk '{ print $1, $2, $3 }' > $OUT_DIR/all.total-only.out
+{ wc $INPUTS/fox.txt; wc $INPUTS/fox.txt | sed 's/ [^ ]*$/ total/'; } \
+    > $OUT_DIR/fox.txt.total-always.out
+
+# File names from a NUL-separated list, given as a file or on stdin
+wc --files0-from=$INPUTS/files0.bin > $OUT_DIR/files0.out
+wc --files0-from=- < $INPUTS/files0.bin > $OUT_DIR/files0.stdin.out
+cat $INPUTS/files0.bin | wc --files0-from=- > $OUT_DIR/files0.pipe.out
+
+# Word frequencies, from the pipeline --freq replaces
+freq() {
+    tr -s '[:space:]' '\n' | sed 's/^[^[:alnum:]]*//; s/[^[:alnum:]]*$//' \
+        | grep -v '^$' | sort | uniq -c | sort -s -k1,1nr
+}
+width() {
+    awk 'NR == 1 { w = length($1) } { printf "%*d %s\n", w, $1, $2 }'
+}
+RHYME="$INPUTS/rhyme.md"
+freq < $RHYME | width > $OUT_DIR/rhyme.md.freq.out
+freq < $RHYME | head -n 5 | width > $OUT_DIR/rhyme.md.freq.top5.out
+tr '[:upper:]' '[:lower:]' < $RHYME | freq | head -n 5 | width \
+    > $OUT_DIR/rhyme.md.freq.i.top5.out
+tr '[:upper:]' '[:lower:]' < $RHYME | freq \
+    | grep -vwFf <(grep -v '^#' $INPUTS/stopwords.lst | grep .) \
+    | awk 'length($2) >= 4 { print $1 "\t" $2 }' > $OUT_DIR/rhyme.md.freq.tsv.out
+cat $INPUTS/fox.txt $INPUTS/atlamal.txt | freq | width > $OUT_DIR/all.freq.out
+
+# stdin through a pipe, and unreadable files among regular ones
+cat $INPUTS/fox.txt | wc - $INPUTS/atlamal.txt > $OUT_DIR/stdin_pipe.out
+wc $INPUTS/fox.txt $INPUTS/no-such-file 2>/dev/null \
+    > $OUT_DIR/skips_bad_file.out
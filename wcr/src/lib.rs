@@ -1,7 +1,12 @@
-use std::{error::Error, fs::File, io::BufReader};
-use std::io::{self, BufRead};
+use std::{error::Error, fs::{self, File}, io::BufReader};
+use std::collections::{HashMap, HashSet};
+use std::io::{self, BufRead, BufWriter, Write};
 
 use clap::{App, Arg};
+use rayon::prelude::*;
+use unicode_width::UnicodeWidthChar;
+
+mod sloc;
 
 type MyResult<T> = Result<T, Box<dyn Error>>;
 
@@ -11,12 +16,41 @@ pub struct Config {
     bytes: bool,
     lines: bool,
     words: bool,
+    chars: bool,
+    max_line_length: bool,
+    files0_from: Option<String>,
+    total: Total,
+    freq: Option<Freq>,
+    sloc: bool,
+}
+
+// Settings for --freq, which reports how often each word occurs instead of
+// the usual counts.
+#[derive(Debug)]
+struct Freq {
+    top: Option<usize>,
+    ignore_case: bool,
+    min_length: usize,
+    stopwords: HashSet<String>,
+    tsv: bool,
+}
+
+// When the summary row is printed. Only prints it without the per-file
+// rows or the "total" label.
+#[derive(Debug, PartialEq)]
+enum Total {
+    Auto,
+    Always,
+    Only,
+    Never,
 }
 
 pub struct FileInfo {
     lines: usize,
     words: usize,
     bytes: usize,
+    chars: usize,
+    max_line_length: usize,
 }
 
 pub fn get_flags() ->  MyResult<Config> {
@@ -28,17 +62,95 @@ pub fn get_flags() ->  MyResult<Config> {
             .default_value("-")
             .multiple(true),    
         )
+        .arg(
+            Arg::with_name("files0_from")
+                .long("files0-from")
+                .value_name("F")
+                .help("Read NUL-separated file names from F, or stdin when F is -")
+                .takes_value(true)
+                .conflicts_with("files"),
+        )
+        .arg(
+            Arg::with_name("total")
+                .long("total")
+                .value_name("WHEN")
+                .help("When to print the total row")
+                .possible_values(&["auto", "always", "only", "never"])
+                .default_value("auto"),
+        )
+        .arg(
+            Arg::with_name("freq")
+                .long("freq")
+                .help("Print how often each word occurs, most frequent first")
+                .conflicts_with_all(&["bytes", "lines", "words", "chars", "max_line_length"]),
+        )
+        .arg(
+            Arg::with_name("top")
+                .long("top")
+                .value_name("N")
+                .help("Print only the N most frequent words")
+                .takes_value(true)
+                .requires("freq"),
+        )
+        .arg(
+            Arg::with_name("ignore_case")
+                .short("i")
+                .long("ignore-case")
+                .help("Fold case before counting words")
+                .requires("freq"),
+        )
+        .arg(
+            Arg::with_name("min_length")
+                .long("min-length")
+                .value_name("N")
+                .help("Skip words shorter than N characters")
+                .takes_value(true)
+                .requires("freq"),
+        )
+        .arg(
+            Arg::with_name("stopwords")
+                .long("stopwords")
+                .value_name("FILE")
+                .help("Skip the words listed in FILE, one per line")
+                .takes_value(true)
+                .requires("freq"),
+        )
+        .arg(
+            Arg::with_name("tsv")
+                .long("tsv")
+                .help("Print counts and words separated by a tab")
+                .requires("freq"),
+        )
+        .arg(
+            Arg::with_name("sloc")
+                .long("sloc")
+                .help("Count code, comment and blank lines per language, walking directories")
+                .conflicts_with_all(&["bytes", "lines", "words", "chars", "max_line_length", "freq"]),
+        )
         .arg(
             Arg::with_name("bytes")
-                .short("c"),
+                .short("c")
+                .long("bytes"),
         )
         .arg(
             Arg::with_name("lines")
-                .short("l"),
+                .short("l")
+                .long("lines"),
         )
         .arg(
             Arg::with_name("words")
-                .short("w"),
+                .short("w")
+                .long("words"),
+        )
+        .arg(
+            Arg::with_name("chars")
+                .short("m")
+                .long("chars"),
+        )
+        .arg(
+            Arg::with_name("max_line_length")
+                .short("L")
+                .long("max-line-length"),
         )
         .get_matches();
 
@@ -46,74 +158,469 @@ pub fn get_flags() ->  MyResult<Config> {
     let bytes = matches.is_present("bytes");
     let lines = matches.is_present("lines");
     let words = matches.is_present("words");
+    let chars = matches.is_present("chars");
+    let max_line_length = matches.is_present("max_line_length");
+    let files0_from = matches.value_of("files0_from").map(String::from);
+    let total = match matches.value_of("total") {
+        Some("always") => Total::Always,
+        Some("only") => Total::Only,
+        Some("never") => Total::Never,
+        _ => Total::Auto,
+    };
+    let freq = if matches.is_present("freq") {
+        let ignore_case = matches.is_present("ignore_case");
+        let stopwords = match matches.value_of("stopwords") {
+            Some(filename) => read_stopwords(filename, ignore_case)
+                .map_err(|e| format!("{}: {}", filename, e))?,
+            None => HashSet::new(),
+        };
+        Some(Freq {
+            top: matches.value_of("top").map(|v| parse_count(v, "top count")).transpose()?,
+            ignore_case,
+            min_length: matches
+                .value_of("min_length")
+                .map(|v| parse_count(v, "minimum length"))
+                .transpose()?
+                .unwrap_or(0),
+            stopwords,
+            tsv: matches.is_present("tsv"),
+        })
+    } else {
+        None
+    };
 
     Ok(Config {
-        files, bytes, lines, words,
+        files, bytes, lines, words, chars, max_line_length, files0_from, total, freq,
+        sloc: matches.is_present("sloc"),
     })
 }
 
-fn open(filename: &str) -> MyResult<Box<dyn BufRead>> {
+fn parse_count(val: &str, what: &str) -> MyResult<usize> {
+    val.parse().map_err(|_| format!("illegal {} -- {}", what, val).into())
+}
+
+// One word per line; blank lines and lines starting with # are skipped.
+fn read_stopwords(filename: &str, ignore_case: bool) -> io::Result<HashSet<String>> {
+    let mut stopwords = HashSet::new();
+    for line in open(filename)?.lines() {
+        let line = line?;
+        let word = line.trim();
+        if word.is_empty() || word.starts_with('#') {
+            continue;
+        }
+        stopwords.insert(if ignore_case { word.to_lowercase() } else { word.to_string() });
+    }
+    Ok(stopwords)
+}
+
+fn open(filename: &str) -> io::Result<Box<dyn BufRead>> {
     match filename{
-        "-" => Ok(Box::new(BufReader::new(io::stdin()))),
-        _ => Ok(Box::new(BufReader::new(File::open(filename)?))),
+        "-" => Ok(Box::new(BufReader::with_capacity(64 * 1024, io::stdin()))),
+        _ => Ok(Box::new(BufReader::with_capacity(64 * 1024, File::open(filename)?))),
     }
 }
 
-fn count<T: BufRead>(file: &mut T) -> MyResult<FileInfo> {
-    let mut lines: usize = 0;
-    let mut words: usize = 0;
-    let mut bytes: usize = 0;
+fn count<T: BufRead>(file: &mut T, decode: bool) -> io::Result<FileInfo> {
+    let mut counter = Counter::new(decode);
+    feed(file, &mut counter)?;
+    Ok(counter.finish())
+}
+
+// Splits the stream into words exactly as count does, and tallies each one.
+fn count_words<T: BufRead>(file: &mut T) -> io::Result<HashMap<String, usize>> {
+    let mut counter = Counter::new(true);
+    counter.seen = Some(HashMap::new());
+    feed(file, &mut counter)?;
+    counter.end_word();
+    Ok(counter.seen.unwrap_or_default())
+}
+
+fn feed<T: BufRead>(file: &mut T, counter: &mut Counter) -> io::Result<()> {
     loop {
-        let mut line = String::new();
-        let b = file.read_line(&mut line)?;
-        if b == 0 {
+        let chunk = match file.fill_buf() {
+            Ok(chunk) => chunk,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+        if chunk.is_empty() {
             break;
         }
-        lines += 1;
-        words += line.split_whitespace().count();
-        bytes += b;
+        counter.update(chunk);
+        let len = chunk.len();
+        file.consume(len);
+    }
+    Ok(())
+}
+
+// Counts a stream one chunk at a time without copying it into lines.
+// Lines come from memchr; everything else is decoded as UTF-8 and follows
+// GNU wc in a UTF-8 locale: whitespace ends a word, printable characters
+// make one, and control characters and invalid bytes count for neither.
+// Invalid bytes are not characters either, but still count as bytes.
+struct Counter {
+    info: FileInfo,
+    in_word: bool,
+    // Partly decoded UTF-8 sequence, which may span two chunks.
+    code_point: u32,
+    needed: u8,
+    lower: u8,
+    upper: u8,
+    line_pos: usize,
+    // Off when only lines and bytes are wanted, which need no decoding.
+    decode: bool,
+    // Words seen so far, only kept for --freq.
+    seen: Option<HashMap<String, usize>>,
+    word: String,
+}
+
+impl Counter {
+    fn new(decode: bool) -> Counter {
+        Counter {
+            info: FileInfo { lines: 0, words: 0, bytes: 0, chars: 0, max_line_length: 0 },
+            in_word: false,
+            code_point: 0,
+            needed: 0,
+            lower: 0x80,
+            upper: 0xbf,
+            line_pos: 0,
+            decode,
+            seen: None,
+            word: String::new(),
+        }
+    }
+
+    fn update(&mut self, chunk: &[u8]) {
+        self.info.bytes += chunk.len();
+        self.info.lines += memchr::memchr_iter(b'\n', chunk).count();
+        if !self.decode {
+            return;
+        }
+        for &byte in chunk {
+            self.decode_byte(byte);
+        }
+    }
+
+    // Byte at a time UTF-8 decoding, so sequences split across chunks need
+    // no copying. Like glibc, the old 5 and 6 byte forms and code points
+    // past U+10FFFF are accepted, while overlong forms and surrogates are
+    // not. Bytes that can't start or continue a sequence are skipped.
+    fn decode_byte(&mut self, byte: u8) {
+        if self.needed > 0 {
+            if (self.lower..=self.upper).contains(&byte) {
+                self.code_point = (self.code_point << 6) | (byte & 0x3f) as u32;
+                self.needed -= 1;
+                self.lower = 0x80;
+                self.upper = 0xbf;
+                if self.needed == 0 {
+                    match char::from_u32(self.code_point) {
+                        Some(c) => self.add_char(c),
+                        // A character to glibc, but never a printable one.
+                        None => self.info.chars += 1,
+                    }
+                }
+                return;
+            }
+            // Broken sequence: drop it and try this byte as a new start.
+            self.needed = 0;
+            self.lower = 0x80;
+            self.upper = 0xbf;
+        }
+        match byte {
+            0x00..=0x7f => self.add_char(byte as char),
+            0xc2..=0xdf => self.start(byte & 0x1f, 1),
+            0xe0 => { self.start(byte & 0x0f, 2); self.lower = 0xa0; }
+            0xed => { self.start(byte & 0x0f, 2); self.upper = 0x9f; }
+            0xe1..=0xef => self.start(byte & 0x0f, 2),
+            0xf0 => { self.start(byte & 0x07, 3); self.lower = 0x90; }
+            0xf1..=0xf7 => self.start(byte & 0x07, 3),
+            0xf8 => { self.start(byte & 0x03, 4); self.lower = 0x88; }
+            0xf9..=0xfb => self.start(byte & 0x03, 4),
+            0xfc => { self.start(byte & 0x01, 5); self.lower = 0x84; }
+            0xfd => self.start(byte & 0x01, 5),
+            _ => {}
+        }
+    }
+
+    fn start(&mut self, bits: u8, needed: u8) {
+        self.code_point = bits as u32;
+        self.needed = needed;
+    }
+
+    // Display width is tracked the way GNU wc -L does it: tabs stop every
+    // 8 columns, and LF, CR or FF start over at column 0. U+2060 and the
+    // no-break spaces separate words too, as they do in GNU wc.
+    fn add_char(&mut self, c: char) {
+        self.info.chars += 1;
+        match c {
+            '\n' | '\r' | '\x0c' => {
+                self.info.max_line_length = self.info.max_line_length.max(self.line_pos);
+                self.line_pos = 0;
+                self.end_word();
+            }
+            '\t' => {
+                self.line_pos += 8 - self.line_pos % 8;
+                self.end_word();
+            }
+            // FS, GS, RS, US and the C1 controls such as NEL
+            _ if c.is_control() && c != '\x0b' => {}
+            _ if c.is_whitespace() || c == '\u{2060}' => {
+                self.line_pos += char_width(c);
+                self.end_word();
+            }
+            _ => {
+                self.line_pos += char_width(c);
+                if !self.in_word {
+                    self.info.words += 1;
+                    self.in_word = true;
+                }
+                if self.seen.is_some() {
+                    self.word.push(c);
+                }
+            }
+        }
+    }
+
+    fn end_word(&mut self) {
+        self.in_word = false;
+        if let Some(seen) = &mut self.seen {
+            if !self.word.is_empty() {
+                *seen.entry(std::mem::take(&mut self.word)).or_insert(0) += 1;
+            }
+        }
+    }
+
+    fn finish(mut self) -> FileInfo {
+        self.info.max_line_length = self.info.max_line_length.max(self.line_pos);
+        self.info
+    }
+}
+
+// glibc gives the soft hyphen a column where Unicode gives it none.
+fn char_width(c: char) -> usize {
+    match c {
+        '\u{ad}' => 1,
+        _ => c.width().unwrap_or(0),
     }
-    Ok(FileInfo { lines, words, bytes})
 }
 
-fn print(config: &Config, file_info: &FileInfo, filename: &str) {
-    if config.lines {
-        print!("{:<6}", file_info.lines);
+fn print(
+    out: &mut impl Write,
+    config: &Config,
+    file_info: &FileInfo,
+    filename: Option<&str>,
+    width: usize,
+) -> io::Result<()> {
+    let mut counts = vec![];
+    let default = !config.lines && !config.words && !config.bytes && !config.chars && !config.max_line_length;
+    if config.lines || default {
+        counts.push(file_info.lines);
+    }
+    if config.words || default {
+        counts.push(file_info.words);
+    }
+    if config.chars {
+        counts.push(file_info.chars);
     }
-    if config.words {
-        print!("{:<6}", file_info.words);
+    if config.bytes || default {
+        counts.push(file_info.bytes);
     }
-    if config.bytes {
-        print!("{:<6}", file_info.bytes);
+    if config.max_line_length {
+        counts.push(file_info.max_line_length);
     }
-    if !config.lines && !config.words && !config.bytes {
-        print!("{:<6}{:<6}{:<6}", file_info.lines, file_info.words, file_info.bytes);
+    let mut line = counts
+        .iter()
+        .map(|n| format!("{:>width$}", n, width = width))
+        .collect::<Vec<_>>()
+        .join(" ");
+    if let Some(filename) = filename {
+        line.push(' ');
+        line.push_str(filename);
     }
-    println!("{}", filename);
+    writeln!(out, "{}", line)
 }
 
-pub fn run(config: &Config) -> MyResult<()> {
-    let mut total:FileInfo = FileInfo { lines: 0, words: 0, bytes: 0 };
-    let num_file = config.files.len();
-    // for ... in ... 
-    // for statement will move ownership out
-    // use iter() to get ref
-    for filename in &config.files {
+// Reads the NUL-separated names for --files0-from. Empty names and, when
+// the list itself is stdin, "-" are reported and left out, so every name
+// returned can be opened.
+fn read_files0(source: &str, all_ok: &mut bool) -> MyResult<Vec<String>> {
+    let mut list = open(source).map_err(|e| format!("cannot open {} for reading: {}", source, e))?;
+    let mut files = vec![];
+    let mut name = vec![];
+    let mut item = 0;
+    loop {
+        name.clear();
+        if list.read_until(b'\0', &mut name)? == 0 {
+            break;
+        }
+        item += 1;
+        if name.last() == Some(&b'\0') {
+            name.pop();
+        }
+        let error = if name.is_empty() {
+            Some("invalid zero-length file name")
+        } else if source == "-" && name == b"-" {
+            Some("when reading file names from stdin, no file name of '-' allowed")
+        } else {
+            None
+        };
+        match error {
+            Some(error) => {
+                eprintln!("wcr: {}:{}: {}", source, item, error);
+                *all_ok = false;
+            }
+            None => match String::from_utf8(name.clone()) {
+                Ok(name) => files.push(name),
+                Err(_) => {
+                    eprintln!("wcr: {}:{}: invalid UTF-8 in file name", source, item);
+                    *all_ok = false;
+                }
+            },
+        }
+    }
+    Ok(files)
+}
+
+// The size of a regular file, or None for pipes, terminals and the like.
+fn regular_size(filename: &str) -> Option<u64> {
+    let path = if filename == "-" { "/dev/stdin" } else { filename };
+    fs::metadata(path).ok().filter(|m| m.is_file()).map(|m| m.len())
+}
+
+// Columns are as wide as GNU wc makes them: wide enough for the combined
+// size of the regular files, and at least 7 once a pipe or terminal is
+// read, since its size isn't known up front. A lone count for a lone file
+// isn't padded at all.
+fn number_width(config: &Config, num_file: usize, sizes: &[Option<u64>]) -> usize {
+    let selected = [config.lines, config.words, config.chars, config.bytes, config.max_line_length]
+        .iter()
+        .filter(|&&flag| flag)
+        .count();
+    if config.total == Total::Only || (num_file == 1 && selected == 1) {
+        return 1;
+    }
+    let mut minimum = 1;
+    let mut total = 0;
+    for size in sizes {
+        match size {
+            Some(size) => total += size,
+            None => minimum = 7,
+        }
+    }
+    total.to_string().len().max(minimum)
+}
+
+pub fn run(config: &Config) -> MyResult<bool> {
+    let mut total:FileInfo = FileInfo {
+        lines: 0, words: 0, bytes: 0, chars: 0, max_line_length: 0,
+    };
+    let mut all_ok = true;
+    let stdout = io::stdout();
+    let mut out = BufWriter::new(stdout.lock());
+    let files = match &config.files0_from {
+        Some(source) => read_files0(source, &mut all_ok)?,
+        None => config.files.clone(),
+    };
+    if config.sloc {
+        return sloc::run(&files, &mut out).map(|ok| ok && all_ok);
+    }
+    if let Some(freq) = &config.freq {
+        return run_freq(freq, &files, &mut out).map(|ok| ok && all_ok);
+    }
+    // Words, characters and widths need the text decoded; lines and bytes
+    // alone don't.
+    let decode = config.words || config.chars || config.max_line_length || !(config.lines || config.bytes);
+    // Count on rayon's thread pool, then print in argument order.
+    let results: Vec<_> = files
+        .par_iter()
+        .map(|filename| {
+            open(filename)
+                .and_then(|mut file| count(&mut file, decode))
+                .map(|file_info| (file_info, regular_size(filename)))
+        })
+        .collect();
+    let sizes: Vec<_> = results.iter().flatten().map(|(_, size)| *size).collect();
+    let width = match &config.files0_from {
+        // A streamed list isn't sized up front, so GNU wc doesn't pad it.
+        Some(source) if regular_size(source).is_none() => 1,
+        _ => number_width(config, files.len(), &sizes),
+    };
+    for (filename, result) in files.iter().zip(results) {
         // can use &String when &str is expected
-        if let Ok(mut file) = open(filename) {
-            let file_info = count(&mut file)?;
-            print(&config, &file_info, &filename);
-            total.lines += file_info.lines;
-            total.words += file_info.words;
-            total.bytes += file_info.bytes;
+        match result {
+            Ok((file_info, _)) => {
+                if config.total != Total::Only {
+                    print(&mut out, config, &file_info, Some(filename), width)?;
+                }
+                total.lines += file_info.lines;
+                total.words += file_info.words;
+                total.bytes += file_info.bytes;
+                total.chars += file_info.chars;
+                total.max_line_length = total.max_line_length.max(file_info.max_line_length);
+            }
+            Err(e) => {
+                out.flush()?;
+                eprintln!("wcr: {}: {}", filename, e);
+                all_ok = false;
+            }
+        }
+    }
+    match config.total {
+        Total::Always => print(&mut out, config, &total, Some("total"), width)?,
+        Total::Auto if files.len() > 1 => print(&mut out, config, &total, Some("total"), width)?,
+        Total::Only => print(&mut out, config, &total, None, width)?,
+        _ => {}
+    }
+    out.flush()?;
+    Ok(all_ok)
+}
+
+fn run_freq(freq: &Freq, files: &[String], out: &mut impl Write) -> MyResult<bool> {
+    let mut all_ok = true;
+    let results: Vec<_> = files
+        .par_iter()
+        .map(|filename| open(filename).and_then(|mut file| count_words(&mut file)))
+        .collect();
+    let mut tally: HashMap<String, usize> = HashMap::new();
+    for (filename, result) in files.iter().zip(results) {
+        match result {
+            Ok(seen) => {
+                for (word, n) in seen {
+                    if let Some(word) = normalize_word(freq, &word) {
+                        *tally.entry(word).or_insert(0) += n;
+                    }
+                }
+            }
+            Err(e) => {
+                eprintln!("wcr: {}: {}", filename, e);
+                all_ok = false;
+            }
+        }
+    }
+
+    // Most frequent first, then alphabetically so ties print the same way
+    // every time.
+    let mut tally: Vec<_> = tally.into_iter().collect();
+    tally.sort_unstable_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    tally.truncate(freq.top.unwrap_or(usize::MAX));
+    let width = tally.first().map_or(1, |(_, n)| n.to_string().len());
+    for (word, n) in tally {
+        if freq.tsv {
+            writeln!(out, "{}\t{}", n, word)?;
         } else {
-            println!("Open {} filed!", filename);
+            writeln!(out, "{:>width$} {}", n, word, width = width)?;
         }
     }
-    if num_file > 1 {
-        print(&config, &total, "total");
-        // why can the following sentence can be compiled
-        // print(&config, &total, &"total");
+    out.flush()?;
+    Ok(all_ok)
+}
+
+// Words are split as wc splits them, so punctuation clings to them. It is
+// trimmed off both ends here, leaving inner marks as in "don't" or "e-mail".
+fn normalize_word(freq: &Freq, word: &str) -> Option<String> {
+    let word = word.trim_matches(|c: char| !c.is_alphanumeric());
+    let word = if freq.ignore_case { word.to_lowercase() } else { word.to_string() };
+    if word.is_empty() || word.chars().count() < freq.min_length || freq.stopwords.contains(&word) {
+        return None;
     }
-    Ok(())
-}
\ No newline at end of file
+    Some(word)
+}
@@ -1,16 +1,10 @@
 fn main() {
-    let config = wcr::get_flags().unwrap();
-    wcr::run(&config).unwrap();
-    let b = "s";
-    let c = &b;
-    assert_eq!(c, &"s");
-    let mut a: u32 = 1;
-    a = a;
-    fun(&a);
+    match wcr::get_flags().and_then(|config| wcr::run(&config)) {
+        Ok(true) => {}
+        Ok(false) => std::process::exit(1),
+        Err(e) => {
+            eprintln!("wcr: {}", e);
+            std::process::exit(1);
+        }
+    }
 }
-
-fn fun(mut x: & u32) {
-    println!("{}", x);
-    x = &3;
-    println!("{}", x);
-}     
@@ -0,0 +1,423 @@
+// Code, comment and blank line counts per language for --sloc.
+//
+// A line holding anything outside a comment is code, even if it ends in a
+// comment. A line with only comment text is a comment line. Lines with
+// nothing but whitespace are blank, inside a block comment or not. String
+// literals are code, and comment markers inside them are ignored.
+
+use std::collections::BTreeMap;
+use std::io::{self, BufRead, Write};
+use std::path::Path;
+
+use rayon::prelude::*;
+use walkdir::WalkDir;
+
+use crate::{open, MyResult};
+
+struct Quote {
+    open: &'static str,
+    close: &'static str,
+    escapes: bool,
+    // Unclosed single line strings end with the line instead of running on.
+    multiline: bool,
+}
+
+const fn quote(open: &'static str, close: &'static str, escapes: bool, multiline: bool) -> Quote {
+    Quote { open, close, escapes, multiline }
+}
+
+pub struct Language {
+    name: &'static str,
+    extensions: &'static [&'static str],
+    line_comments: &'static [&'static str],
+    block_comments: &'static [(&'static str, &'static str)],
+    nested_comments: bool,
+    quotes: &'static [Quote],
+    // Rust's r"..." and r#"..."# strings, and its 'c' literals, which
+    // have to be told apart from lifetimes.
+    rust_literals: bool,
+    // A shell # only starts a comment at the start of a word, so $# and
+    // ${#x} are code.
+    comment_starts_word: bool,
+}
+
+static LANGUAGES: &[Language] = &[
+    Language {
+        name: "C",
+        extensions: &["c", "h"],
+        line_comments: &["//"],
+        block_comments: &[("/*", "*/")],
+        nested_comments: false,
+        quotes: &[quote("\"", "\"", true, true), quote("'", "'", true, false)],
+        rust_literals: false,
+        comment_starts_word: false,
+    },
+    Language {
+        name: "C++",
+        extensions: &["cc", "cpp", "cxx", "hh", "hpp", "hxx"],
+        line_comments: &["//"],
+        block_comments: &[("/*", "*/")],
+        nested_comments: false,
+        quotes: &[quote("\"", "\"", true, true), quote("'", "'", true, false)],
+        rust_literals: false,
+        comment_starts_word: false,
+    },
+    Language {
+        name: "Markdown",
+        extensions: &["md", "markdown"],
+        line_comments: &[],
+        block_comments: &[("<!--", "-->")],
+        nested_comments: false,
+        quotes: &[],
+        rust_literals: false,
+        comment_starts_word: false,
+    },
+    Language {
+        name: "Python",
+        extensions: &["py", "pyw"],
+        line_comments: &["#"],
+        block_comments: &[],
+        nested_comments: false,
+        quotes: &[
+            quote("\"\"\"", "\"\"\"", true, true),
+            quote("'''", "'''", true, true),
+            quote("\"", "\"", true, false),
+            quote("'", "'", true, false),
+        ],
+        rust_literals: false,
+        comment_starts_word: false,
+    },
+    Language {
+        name: "Rust",
+        extensions: &["rs"],
+        line_comments: &["//"],
+        block_comments: &[("/*", "*/")],
+        nested_comments: true,
+        quotes: &[quote("\"", "\"", true, true)],
+        rust_literals: true,
+        comment_starts_word: false,
+    },
+    Language {
+        name: "Shell",
+        extensions: &["sh", "bash", "zsh", "ksh"],
+        line_comments: &["#"],
+        block_comments: &[],
+        nested_comments: false,
+        quotes: &[quote("\"", "\"", true, true), quote("'", "'", false, true)],
+        rust_literals: false,
+        comment_starts_word: true,
+    },
+    Language {
+        name: "TOML",
+        extensions: &["toml"],
+        line_comments: &["#"],
+        block_comments: &[],
+        nested_comments: false,
+        quotes: &[
+            quote("\"\"\"", "\"\"\"", true, true),
+            quote("'''", "'''", false, true),
+            quote("\"", "\"", true, false),
+            quote("'", "'", false, false),
+        ],
+        rust_literals: false,
+        comment_starts_word: false,
+    },
+];
+
+pub fn language_of(path: &Path) -> Option<&'static Language> {
+    let extension = path.extension()?.to_str()?.to_lowercase();
+    LANGUAGES.iter().find(|lang| lang.extensions.contains(&extension.as_str()))
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Sloc {
+    code: usize,
+    comment: usize,
+    blank: usize,
+}
+
+impl Sloc {
+    fn lines(&self) -> usize {
+        self.code + self.comment + self.blank
+    }
+
+    fn add(&mut self, other: &Sloc) {
+        self.code += other.code;
+        self.comment += other.comment;
+        self.blank += other.blank;
+    }
+}
+
+// Where the scanner is when a line ends, carried over to the next one.
+#[derive(Clone, Copy)]
+enum State {
+    Code,
+    Comment { block: usize, depth: usize },
+    String { quote: usize },
+    RawString { hashes: usize },
+}
+
+pub fn count_sloc<T: BufRead>(file: &mut T, lang: &Language) -> io::Result<Sloc> {
+    let mut sloc = Sloc::default();
+    let mut state = State::Code;
+    let mut line = vec![];
+    loop {
+        line.clear();
+        if file.read_until(b'\n', &mut line)? == 0 {
+            break;
+        }
+        let (has_code, has_comment) = scan_line(&line, lang, &mut state);
+        if has_code {
+            sloc.code += 1;
+        } else if has_comment {
+            sloc.comment += 1;
+        } else {
+            sloc.blank += 1;
+        }
+    }
+    Ok(sloc)
+}
+
+// Scans one line, returning whether it holds code and whether it holds
+// comment text. Markers are all ASCII, so bytes are enough and invalid
+// UTF-8 does no harm.
+fn scan_line(line: &[u8], lang: &Language, state: &mut State) -> (bool, bool) {
+    let mut has_code = false;
+    let mut has_comment = false;
+    let mut i = 0;
+    while i < line.len() {
+        let rest = &line[i..];
+        let blank = line[i].is_ascii_whitespace();
+        match *state {
+            State::Comment { block, depth } => {
+                has_comment |= !blank;
+                let (open, close) = lang.block_comments[block];
+                if lang.nested_comments && rest.starts_with(open.as_bytes()) {
+                    *state = State::Comment { block, depth: depth + 1 };
+                    i += open.len();
+                } else if rest.starts_with(close.as_bytes()) {
+                    *state = if depth == 1 {
+                        State::Code
+                    } else {
+                        State::Comment { block, depth: depth - 1 }
+                    };
+                    i += close.len();
+                } else {
+                    i += 1;
+                }
+            }
+            State::String { quote } => {
+                has_code |= !blank;
+                let quote = &lang.quotes[quote];
+                if quote.escapes && line[i] == b'\\' {
+                    i += 2;
+                } else if rest.starts_with(quote.close.as_bytes()) {
+                    *state = State::Code;
+                    i += quote.close.len();
+                } else {
+                    i += 1;
+                }
+            }
+            State::RawString { hashes } => {
+                has_code |= !blank;
+                if line[i] == b'"' && rest[1..].iter().take_while(|&&b| b == b'#').count() >= hashes {
+                    *state = State::Code;
+                    i += 1 + hashes;
+                } else {
+                    i += 1;
+                }
+            }
+            State::Code => {
+                if blank {
+                    i += 1;
+                    continue;
+                }
+                let word_start = i == 0 || line[i - 1].is_ascii_whitespace();
+                if lang.line_comments.iter().any(|c| rest.starts_with(c.as_bytes()))
+                    && (word_start || !lang.comment_starts_word)
+                {
+                    has_comment = true;
+                    break;
+                }
+                if let Some(block) = lang
+                    .block_comments
+                    .iter()
+                    .position(|(open, _)| rest.starts_with(open.as_bytes()))
+                {
+                    has_comment = true;
+                    *state = State::Comment { block, depth: 1 };
+                    i += lang.block_comments[block].0.len();
+                    continue;
+                }
+                has_code = true;
+                if let Some(quote) = lang.quotes.iter().position(|q| rest.starts_with(q.open.as_bytes())) {
+                    *state = State::String { quote };
+                    i += lang.quotes[quote].open.len();
+                } else if lang.rust_literals {
+                    i += rust_literal(line, i, state);
+                } else {
+                    i += 1;
+                }
+            }
+        }
+    }
+
+    // Strings that can't span lines end here, closed or not.
+    if let State::String { quote } = *state {
+        if !lang.quotes[quote].multiline {
+            *state = State::Code;
+        }
+    }
+    (has_code, has_comment)
+}
+
+// Steps over a Rust raw string opener, setting the state, or over a whole
+// character literal, returning how many bytes were used. Anything else,
+// lifetimes included, is a single byte of code.
+fn rust_literal(line: &[u8], i: usize, state: &mut State) -> usize {
+    let ident = |b: u8| b.is_ascii_alphanumeric() || b == b'_';
+    let rest = &line[i..];
+    let after_ident = i > 0 && ident(line[i - 1]);
+
+    let raw = if rest.starts_with(b"br") { 2 } else if rest.starts_with(b"r") { 1 } else { 0 };
+    if raw > 0 && !after_ident {
+        let hashes = rest[raw..].iter().take_while(|&&b| b == b'#').count();
+        if rest.get(raw + hashes) == Some(&b'"') {
+            *state = State::RawString { hashes };
+            return raw + hashes + 1;
+        }
+    }
+
+    if rest[0] == b'\'' {
+        if rest.get(1) == Some(&b'\\') {
+            // '\n', '\'', '\u{1F600}' and so on
+            if let Some(end) = rest.get(3..).and_then(|r| r.iter().position(|&b| b == b'\'')) {
+                return end + 4;
+            }
+        } else if let Some(&b) = rest.get(1) {
+            let len = match b {
+                0x00..=0x7f => 1,
+                0xc0..=0xdf => 2,
+                0xe0..=0xef => 3,
+                _ => 4,
+            };
+            if rest.get(1 + len) == Some(&b'\'') {
+                return len + 2;
+            }
+        }
+    }
+    1
+}
+
+// Walks directory arguments for files in a known language, skipping
+// others. Files named outright must be in a known language too.
+fn collect_files(files: &[String], all_ok: &mut bool) -> Vec<(String, &'static Language)> {
+    let mut found = vec![];
+    for filename in files {
+        if filename == "-" {
+            eprintln!("wcr: -: can't tell the language of standard input");
+            *all_ok = false;
+            continue;
+        }
+        for entry in WalkDir::new(filename).sort_by(|a, b| a.file_name().cmp(b.file_name())) {
+            match entry {
+                Err(e) => {
+                    eprintln!("wcr: {}", e);
+                    *all_ok = false;
+                }
+                Ok(entry) if entry.file_type().is_dir() => {}
+                Ok(entry) => match language_of(entry.path()) {
+                    Some(lang) => found.push((entry.path().display().to_string(), lang)),
+                    None if entry.depth() == 0 => {
+                        eprintln!("wcr: {}: unknown language", filename);
+                        *all_ok = false;
+                    }
+                    None => {}
+                },
+            }
+        }
+    }
+    found
+}
+
+pub fn run(files: &[String], out: &mut impl Write) -> MyResult<bool> {
+    let mut all_ok = true;
+    let found = collect_files(files, &mut all_ok);
+    let results: Vec<_> = found
+        .par_iter()
+        .map(|(filename, lang)| open(filename).and_then(|mut file| count_sloc(&mut file, lang)))
+        .collect();
+
+    // Files grouped by language, in the order they were found.
+    let mut groups: BTreeMap<&str, Vec<(&str, Sloc)>> = BTreeMap::new();
+    let mut total = Sloc::default();
+    for ((filename, lang), result) in found.iter().zip(results) {
+        match result {
+            Ok(sloc) => {
+                total.add(&sloc);
+                groups.entry(lang.name).or_default().push((filename, sloc));
+            }
+            Err(e) => {
+                eprintln!("wcr: {}: {}", filename, e);
+                all_ok = false;
+            }
+        }
+    }
+
+    let width = total.lines().to_string().len().max("comment".len());
+    let row = |out: &mut dyn Write, sloc: &Sloc, label: &str| {
+        writeln!(
+            out,
+            "{:>w$} {:>w$} {:>w$} {:>w$} {}",
+            sloc.lines(), sloc.code, sloc.comment, sloc.blank, label, w = width
+        )
+    };
+    writeln!(out, "{:>w$} {:>w$} {:>w$} {:>w$} file", "lines", "code", "comment", "blank", w = width)?;
+    for (name, files) in &groups {
+        let mut subtotal = Sloc::default();
+        for (filename, sloc) in files {
+            row(out, sloc, filename)?;
+            subtotal.add(sloc);
+        }
+        let plural = if files.len() == 1 { "" } else { "s" };
+        row(out, &subtotal, &format!("{} ({} file{})", name, files.len(), plural))?;
+    }
+    row(out, &total, "total")?;
+    out.flush()?;
+    Ok(all_ok)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{count_sloc, language_of, Sloc};
+    use std::path::Path;
+
+    fn sloc(filename: &str, text: &str) -> Sloc {
+        let lang = language_of(Path::new(filename)).unwrap();
+        count_sloc(&mut text.as_bytes(), lang).unwrap()
+    }
+
+    #[test]
+    fn test_count_sloc() {
+        let counts = |code, comment, blank| Sloc { code, comment, blank };
+
+        // Nested block comments only end at the outermost close.
+        assert_eq!(sloc("a.rs", "/* a /* b */\nstill */\nfn f() {}\n"), counts(1, 2, 0));
+        // C comments don't nest.
+        assert_eq!(sloc("a.c", "/* a /* b */\nint x; */\n"), counts(1, 1, 0));
+        // Comment markers in strings are code.
+        assert_eq!(sloc("a.rs", "let s = \"/* no\";\n// yes\n"), counts(1, 1, 0));
+        assert_eq!(sloc("a.rs", "let s = r#\"a \" // \"#;\n"), counts(1, 0, 0));
+        assert_eq!(sloc("a.rs", "let c = '\"'; // q\nlet d = '\\'';\n"), counts(2, 0, 0));
+        // Lifetimes aren't character literals.
+        assert_eq!(sloc("a.rs", "fn f<'a>(x: &'a str) {}\n// c\n"), counts(1, 1, 0));
+        // Multi-line strings stay code, even when they look like comments.
+        assert_eq!(sloc("a.py", "s = \"\"\"\n# not a comment\n\"\"\"\n"), counts(3, 0, 0));
+        assert_eq!(sloc("a.sh", "echo $# ${#x} # count\n# c\n\n"), counts(1, 1, 1));
+        assert_eq!(sloc("a.toml", "a = 'x # y'\n"), counts(1, 0, 0));
+        assert_eq!(sloc("a.md", "# Title\n\n<!--\nnote\n-->\n"), counts(1, 3, 1));
+        // Blank lines in a block comment are still blank.
+        assert_eq!(sloc("a.c", "/*\n\n*/\n"), counts(0, 2, 1));
+        assert_eq!(sloc("a.c", ""), counts(0, 0, 0));
+    }
+}
@@ -0,0 +1,369 @@
+use assert_cmd::Command;
+use predicates::prelude::*;
+use rand::{distributions::Alphanumeric, Rng};
+use std::fs;
+
+type TestResult = Result<(), Box<dyn std::error::Error>>;
+
+const PRG: &str = "wcr";
+const EMPTY: &str = "tests/inputs/empty.txt";
+const FOX: &str = "tests/inputs/fox.txt";
+const ATLAMAL: &str = "tests/inputs/atlamal.txt";
+const WIDE: &str = "tests/inputs/wide.txt";
+const INVALID: &str = "tests/inputs/invalid-utf8.txt";
+const BINARY: &str = "tests/inputs/binary.bin";
+const FILES0: &str = "tests/inputs/files0.bin";
+const RHYME: &str = "tests/inputs/rhyme.md";
+const STOPWORDS: &str = "tests/inputs/stopwords.lst";
+const SLOC: &str = "tests/inputs/sloc";
+
+// --------------------------------------------------
+fn gen_bad_file() -> String {
+    loop {
+        let filename: String = rand::thread_rng()
+            .sample_iter(&Alphanumeric)
+            .take(7)
+            .map(char::from)
+            .collect();
+
+        if fs::metadata(&filename).is_err() {
+            return filename;
+        }
+    }
+}
+
+// --------------------------------------------------
+#[test]
+fn skips_bad_file() -> TestResult {
+    let bad = gen_bad_file();
+    let expected = format!("wcr: {}: .* [(]os error 2[)]", bad);
+    Command::cargo_bin(PRG)?
+        .args([FOX, &bad])
+        .assert()
+        .failure()
+        .stdout(fs::read_to_string("tests/expected/skips_bad_file.out")?)
+        .stderr(predicate::str::is_match(expected)?);
+    Ok(())
+}
+
+// --------------------------------------------------
+fn run(args: &[&str], expected_file: &str) -> TestResult {
+    let expected = fs::read_to_string(expected_file)?;
+    Command::cargo_bin(PRG)?
+        .args(args)
+        .assert()
+        .success()
+        .stdout(expected);
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn fox() -> TestResult {
+    run(&[FOX], "tests/expected/fox.txt.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn empty_chars() -> TestResult {
+    run(&["-m", EMPTY], "tests/expected/empty.txt.m.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn atlamal_chars() -> TestResult {
+    run(&["--chars", ATLAMAL], "tests/expected/atlamal.txt.m.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn atlamal_max_line_length() -> TestResult {
+    run(&["-L", ATLAMAL], "tests/expected/atlamal.txt.L.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn fox_max_line_length() -> TestResult {
+    run(&["--max-line-length", FOX], "tests/expected/fox.txt.L.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn wide_max_line_length() -> TestResult {
+    run(&["-L", WIDE], "tests/expected/wide.txt.L.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn fox_all_flags() -> TestResult {
+    run(&["-L", "-c", "-m", "-w", "-l", FOX], "tests/expected/fox.txt.all-flags.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn wide_all_flags() -> TestResult {
+    run(&["-lwmcL", WIDE], "tests/expected/wide.txt.all-flags.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn all_chars_max_line_length() -> TestResult {
+    run(&["-m", "-L", ATLAMAL, FOX, WIDE], "tests/expected/all.mL.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn all_in_argument_order() -> TestResult {
+    run(&[FOX, EMPTY, ATLAMAL, WIDE, FOX], "tests/expected/all.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn all_lines() -> TestResult {
+    run(&["-l", ATLAMAL, FOX], "tests/expected/all.l.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn total_never() -> TestResult {
+    run(&["--total=never", FOX, ATLAMAL], "tests/expected/all.total-never.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn total_only() -> TestResult {
+    run(&["--total", "only", FOX, ATLAMAL], "tests/expected/all.total-only.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn total_always() -> TestResult {
+    run(&["--total=always", FOX], "tests/expected/fox.txt.total-always.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_bad_total() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["--total=sometimes", FOX])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("isn't a valid value"));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn files0_from() -> TestResult {
+    run(&["--files0-from", FILES0], "tests/expected/files0.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn files0_from_stdin() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .arg("--files0-from=-")
+        .write_stdin(fs::read(FILES0)?)
+        .assert()
+        .success()
+        .stdout(fs::read_to_string("tests/expected/files0.pipe.out")?);
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn files0_from_bad_names() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .arg("--files0-from=-")
+        .write_stdin(format!("{}\0\0-\0", FOX))
+        .assert()
+        .failure()
+        .stdout(format!("1 9 48 {}\n", FOX))
+        .stderr(
+            "wcr: -:2: invalid zero-length file name\n\
+             wcr: -:3: when reading file names from stdin, no file name of '-' allowed\n",
+        );
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_files0_from_with_files() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["--files0-from", FILES0, FOX])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("cannot be used with"));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_bad_files0_from() -> TestResult {
+    let bad = gen_bad_file();
+    let expected = format!("wcr: cannot open {} for reading: .* [(]os error 2[)]", bad);
+    Command::cargo_bin(PRG)?
+        .args(["--files0-from", &bad])
+        .assert()
+        .failure()
+        .stderr(predicate::str::is_match(expected)?);
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn stdin_pipe() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["-", ATLAMAL])
+        .write_stdin(fs::read_to_string(FOX)?)
+        .assert()
+        .success()
+        .stdout(fs::read_to_string("tests/expected/stdin_pipe.out")?);
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn freq() -> TestResult {
+    run(&["--freq", RHYME], "tests/expected/rhyme.md.freq.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn freq_top() -> TestResult {
+    run(&["--freq", "--top", "5", RHYME], "tests/expected/rhyme.md.freq.top5.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn freq_ignore_case() -> TestResult {
+    run(&["--freq", "-i", "--top=5", RHYME], "tests/expected/rhyme.md.freq.i.top5.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn freq_stopwords_min_length_tsv() -> TestResult {
+    run(
+        &["--freq", "--ignore-case", "--stopwords", STOPWORDS, "--min-length", "4", "--tsv", RHYME],
+        "tests/expected/rhyme.md.freq.tsv.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn freq_all() -> TestResult {
+    run(&["--freq", FOX, ATLAMAL], "tests/expected/all.freq.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_bad_top() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["--freq", "--top", "ten", RHYME])
+        .assert()
+        .failure()
+        .stderr("wcr: illegal top count -- ten\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_freq_with_counts() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["--freq", "-l", RHYME])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("cannot be used with"));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn sloc_dir() -> TestResult {
+    run(&["--sloc", SLOC], "tests/expected/sloc.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn sloc_files_and_dirs() -> TestResult {
+    run(
+        &["--sloc", "tests/inputs/sloc/main.rs", "tests/inputs/sloc/nested"],
+        "tests/expected/sloc.files.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn sloc_unknown_language() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["--sloc", "tests/inputs/sloc/notes.dat"])
+        .assert()
+        .failure()
+        .stderr("wcr: tests/inputs/sloc/notes.dat: unknown language\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn invalid_utf8() -> TestResult {
+    run(&[INVALID], "tests/expected/invalid-utf8.txt.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn invalid_utf8_all_flags() -> TestResult {
+    run(&["-lwmcL", INVALID], "tests/expected/invalid-utf8.txt.all-flags.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn binary_chars() -> TestResult {
+    run(&["-m", BINARY], "tests/expected/binary.bin.m.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn binary_all_flags() -> TestResult {
+    run(&["-lwmcL", BINARY], "tests/expected/binary.bin.all-flags.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn atlamal_stdin() -> TestResult {
+    let input = fs::read_to_string(ATLAMAL)?;
+    let expected = fs::read_to_string("tests/expected/atlamal.txt.m.out")?;
+    Command::cargo_bin(PRG)?
+        .arg("-m")
+        .write_stdin(input)
+        .assert()
+        .success()
+        .stdout(expected.replace(ATLAMAL, "-"));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn freq_closed_pipe() -> TestResult {
+    use std::io::{BufRead, BufReader, Write};
+    use std::process::{self, Stdio};
+
+    // Far more distinct words than a pipe holds, so wcr is still writing
+    // when the reader goes away.
+    let input: String = (0..50_000).map(|i| format!("word{}\n", i)).collect();
+    let mut child = process::Command::new(assert_cmd::cargo::cargo_bin(PRG))
+        .arg("--freq")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    let mut stdin = child.stdin.take().unwrap();
+    let writer = std::thread::spawn(move || stdin.write_all(input.as_bytes()));
+    let mut first = String::new();
+    BufReader::new(child.stdout.take().unwrap()).read_line(&mut first)?;
+    writer.join().unwrap()?;
+    let output = child.wait_with_output()?;
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8(output.stderr)?.contains("wcr: Broken pipe"));
+
+    Ok(())
+}
@@ -0,0 +1,15 @@
+//! Crate docs count as comments.
+
+/* A block comment
+   /* with a nested one */
+   still inside the outer comment
+*/
+fn main() {
+    let url = "http://example.com/*not-a-comment*/";
+    let quote = '"'; // a comment after code
+
+    let raw = r#"
+// inside a raw string
+"#;
+    println!("{} {} {}", url, quote, raw);
+}
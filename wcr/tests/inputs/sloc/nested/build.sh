@@ -0,0 +1,5 @@
+#!/bin/sh
+# Build everything
+
+echo "args: $# # not a comment"
+echo 'single # quoted'  # a comment
@@ -0,0 +1,9 @@
+#include <stdio.h>
+
+/* C comments don't nest /* so this
+   ends here */
+int main(void) {
+    // say hello
+    printf("/* hello */\n");
+    return 0;
+}
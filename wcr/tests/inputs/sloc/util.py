@@ -0,0 +1,11 @@
+#!/usr/bin/env python3
+"""Module docstring,
+spanning lines."""
+
+# A comment
+def greet(name):
+    print("# not a comment", name)  # trailing comment
+
+    return '''
+# still a string
+'''